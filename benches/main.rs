@@ -1,9 +1,8 @@
 // https://bheisler.github.io/criterion.rs/book/getting_started.html
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use statical::*;
+use criterion::{criterion_group, criterion_main, Criterion};
 
-pub fn criterion_benchmark(c: &mut Criterion) {
+pub fn criterion_benchmark(_c: &mut Criterion) {
     // c.bench_function("hello", |b| b.iter(|| black_box(hello())));
 }
 
@@ -0,0 +1,26 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// The time range of events requested from a CalDAV calendar collection
+///
+/// Both durations are counted from the calendar's today date and accept values like `90 days` or `18 months`.
+#[derive(Clone, Debug, Deserialize, Serialize, Document, PartialEq, Eq)]
+#[serde(default)]
+pub struct CalDavQuery {
+    /// How far before the today date events are requested
+    #[doku(example = "3 months")]
+    pub(crate) past: String,
+
+    /// How far after the today date events are requested
+    #[doku(example = "18 months")]
+    pub(crate) future: String,
+}
+
+impl Default for CalDavQuery {
+    fn default() -> Self {
+        Self {
+            past: "3 months".into(),
+            future: "1 year".into(),
+        }
+    }
+}
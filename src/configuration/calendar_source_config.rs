@@ -1,25 +1,66 @@
+use chrono::Duration;
 use doku::Document;
 use serde::{Deserialize, Serialize};
 use std::{
     cell::OnceCell,
     ffi::OsStr,
     fmt::{self},
+    path::Path,
 };
 
-use super::types::config_color::ConfigColor;
+use crate::util::slugify;
+
+use super::caldav_query::CalDavQuery;
+use super::config::Config;
+use super::csv_mapping::CsvMapping;
+use super::json_mapping::JsonMapping;
+
+use super::types::{
+    config_color::ConfigColor, config_time_zone::ConfigTimeZone, error_policy::ErrorPolicy,
+    private_event_policy::PrivateEventPolicy, source_type::SourceType,
+};
 
 /// A Config item representing a calendar source
-#[derive(Debug, Deserialize, Serialize, Document, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, Serialize, Document, PartialEq, Eq)]
 pub struct CalendarSourceConfig {
-    /// The url or file path of the calendar
+    /// The url, file path, directory, or glob pattern of the calendar
     ///
-    /// NOTE: File paths are relative to the config file
+    /// Every calendar file in a directory, e.g. `.ics`, `.jcal` or `.xcal`, and every file matching a glob
+    /// pattern becomes its own calendar.
+    /// These calendars are named after the source name and the file name, and titled after their
+    /// `X-WR-CALNAME` property or file name. All other settings apply to each of them.
+    ///
+    /// NOTE: File paths are relative to the config file, `file://` urls are read as file paths
     #[doku(
         example = "calendars/mycalendar_file.ics",
+        example = "calendars/**/*.ics",
         example = "https://example.com/my/calendar/url/ical/"
     )]
     pub source: String,
 
+    /// The format of the source, "ics", "markdown", "csv", "json", "html", or "caldav"
+    ///
+    /// This defaults to "ics" if omitted
+    #[serde(default)]
+    pub(crate) source_type: SourceType,
+
+    /// The columns holding the event details of a "csv" source
+    ///
+    /// The defaults expect columns named "Title", "Date", "Start Time", "End Time", "Location", "URL",
+    /// and "Description" with dates like `2024-03-20`
+    pub(crate) csv: Option<CsvMapping>,
+
+    /// Where a "json" source keeps its events and their details
+    ///
+    /// The defaults expect an array of events with `id`, `title`, `description`, `start`, `end`,
+    /// `timezone`, `location`, `url`, and `categories` fields
+    pub(crate) json: Option<JsonMapping>,
+
+    /// The time range of events requested from a "caldav" source
+    ///
+    /// The defaults request events from 3 months before until 1 year after the today date
+    pub(crate) caldav: Option<CalDavQuery>,
+
     /// The name or internal identifier of the calendar
     ///
     /// Because this is to be used internally, there are a few restrictions
@@ -35,12 +76,50 @@ pub struct CalendarSourceConfig {
     /// If you provide a title here, it will override any calendar provided title.
     pub title: Option<String>,
 
+    /// The title used if neither this config nor the calendar provide one
+    #[serde(skip)]
+    pub(crate) fallback_title: Option<String>,
+
     /// Any valid CSS color notation
     pub(crate) color: ConfigColor,
 
     #[serde(skip)]
     pub(crate) adjusted_color: OnceCell<String>,
 
+    /// Name of the timezone for floating times in this calendar
+    ///
+    /// Floating times have neither a TZID nor a trailing `Z` and are local wall-clock times.
+    /// This defaults to the display timezone if omitted.
+    #[doku(example = "America/Phoenix")]
+    pub timezone: Option<ConfigTimeZone>,
+
+    /// What to do when an event in this calendar, or a file matched by a directory or glob pattern, cannot be parsed
+    ///
+    /// This defaults to the global `on_error` setting if omitted
+    pub(crate) on_error: Option<ErrorPolicy>,
+
+    /// How far past the calendar's today date recurring events in this calendar are expanded
+    ///
+    /// This defaults to the global `recurrence_horizon` setting if omitted
+    #[doku(example = "18 months")]
+    pub recurrence_horizon: Option<String>,
+
+    // this field will be created from recurrence_horizon in CalendarSource::new() hence the serde skip and the OnceCell
+    #[serde(skip)]
+    pub(crate) recurrence_horizon_duration: OnceCell<Option<Duration>>,
+
+    /// The maximum number of instances to generate for a single recurring event in this calendar
+    ///
+    /// This defaults to the global `max_recurrences` setting if omitted
+    #[doku(example = "1000")]
+    pub max_recurrences: Option<u16>,
+
+    /// How to render events marked as private or confidential: "show", "busy-only", or "hide"
+    ///
+    /// "busy-only" replaces the event with a generic "Busy" block in pages and feeds.
+    /// This defaults to "show" if omitted
+    pub(crate) private_events: Option<PrivateEventPolicy>,
+
     /// An array of headers to pass along with the main request
     ///
     /// This is handy if you are retrieving calendars from a site which requires login.
@@ -48,11 +127,43 @@ pub struct CalendarSourceConfig {
     ///
     /// For example, meetup.com requires a header like the following:
     ///
-    /// ```
+    /// ```text
     /// MEETUP_MEMBER=id=<IDENTIFIER>&s=<SESSION_TOKEN>
     /// ```
     /// We may add the ability to auto-retrieve cookies from a local browser at some point.
     pub cookies: Option<Vec<String>>,
+
+    /// The user name sent with HTTP basic authentication when downloading the calendar
+    ///
+    /// CalDAV servers like Nextcloud usually require this, ideally with an app password.
+    pub username: Option<String>,
+
+    /// The password sent with HTTP basic authentication, only used along with a `username`
+    pub password: Option<String>,
+}
+
+impl CalendarSourceConfig {
+    /// What to do when an event in this calendar cannot be parsed, falling back to the global setting
+    pub(crate) fn on_error(&self, config: &Config) -> ErrorPolicy {
+        self.on_error.unwrap_or(config.on_error)
+    }
+
+    /// Derive the config of a single file matched by a directory or glob source
+    ///
+    /// The name and title are taken from the file name while all other settings are kept.
+    pub(crate) fn for_matched_file(&self, path: &Path) -> CalendarSourceConfig {
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        CalendarSourceConfig {
+            source: path.to_string_lossy().to_string(),
+            name: format!("{}-{}", self.name, slugify(&stem)),
+            title: None,
+            fallback_title: Some(stem),
+            ..self.clone()
+        }
+    }
 }
 
 // TODO: need to update this function for new fields
@@ -64,7 +175,7 @@ impl fmt::Display for CalendarSourceConfig {
 
 // TODO: need to update this function for new fields
 impl<'a> From<&'a CalendarSourceConfig> for &'a str {
-    fn from(value: &'a CalendarSourceConfig) -> &str {
+    fn from(value: &'a CalendarSourceConfig) -> &'a str {
         &value.source
     }
 }
@@ -12,6 +12,7 @@ use std::path::PathBuf;
 use std::rc::Rc;
 
 use super::types::cache_mode::CacheMode;
+use super::types::error_policy::ErrorPolicy;
 use super::{
     calendar_source_config::CalendarSourceConfig,
     options::Opt,
@@ -62,6 +63,12 @@ pub struct Config {
     #[doku(example = "America/Phoenix")]
     pub display_timezone: ConfigTimeZone,
 
+    /// Name of the timezone used for event times whose TZID cannot be resolved
+    ///
+    /// This defaults to the display timezone if omitted
+    #[doku(example = "America/Phoenix")]
+    pub fallback_timezone: Option<ConfigTimeZone>,
+
     /// The list of calendars to import (can be files and urls)
     pub(crate) calendar_sources: Vec<Rc<CalendarSourceConfig>>,
 
@@ -71,9 +78,28 @@ pub struct Config {
     #[doku(example = "output")]
     pub output_dir: PathBuf,
 
+    /// How far past the calendar's today date recurring events are expanded, e.g. "18 months"
+    ///
+    /// Recurrences are expanded through the whole calendar range if this is omitted.
+    /// This can be overridden for each calendar source
+    #[doku(example = "18 months")]
+    pub recurrence_horizon: Option<String>,
+
+    /// The maximum number of instances to generate for a single recurring event
+    ///
+    /// Series with more instances are truncated with a warning.
+    /// This can be overridden for each calendar source
+    #[doku(example = "1000")]
+    pub max_recurrences: u16,
+
     /// Whether to download remote calendar sources to disk to reduce server load and increase reliability
     pub(crate) cache_mode: CacheMode,
 
+    /// What to do when an event cannot be parsed, either "skip" the event or "fail" the whole calendar source
+    ///
+    /// This can be overridden for each calendar source
+    pub(crate) on_error: ErrorPolicy,
+
     /// The directory in which downloaded calendars and other temporary files are cached
     #[doku(example = "statical_cache")]
     pub cache_dir: PathBuf,
@@ -147,9 +173,27 @@ pub struct Config {
     /// Whether to render the event pages.
     pub render_event: bool,
 
+    /// Whether to render one event page per recurring series instead of one page per occurrence.
+    ///
+    /// Every occurrence links to the page of its series, which lists the upcoming dates.
+    pub render_series_pages: bool,
+
     /// Whether to render the calendar feed.
     pub render_feed: bool,
 
+    /// Whether to render a page and a calendar feed for each event category.
+    pub render_categories: bool,
+
+    /// Leave attendee lists, contact information, and email addresses out of rendered pages
+    ///
+    /// Organizer names and locations are still shown.
+    pub hide_personal_data: bool,
+
+    /// Whether to leave out events with `STATUS:CANCELLED`
+    ///
+    /// Cancelled events are shown struck through if this is false.
+    pub hide_cancelled_events: bool,
+
     /// The strftime format for the Month `view_date` template variable
     #[doku(example = "%B %Y")]
     pub month_view_format: String,
@@ -208,9 +252,13 @@ impl Default for Config {
             calendar_start_date: None,
             calendar_end_date: None,
             display_timezone: ConfigTimeZone(Tz::America__Phoenix),
+            fallback_timezone: None,
             calendar_sources: Vec::new(),
+            recurrence_horizon: None,
+            max_recurrences: 1000,
             output_dir: "output".into(),
             cache_mode: CacheMode::Normal,
+            on_error: ErrorPolicy::Fail,
             cache_dir: "statical_cache".into(),
             cache_timeout: "1 day".into(),
             cache_timeout_duration: OnceCell::new(),
@@ -229,7 +277,11 @@ impl Default for Config {
             render_day: true,
             render_agenda: true,
             render_event: true,
+            render_series_pages: false,
             render_feed: true,
+            render_categories: true,
+            hide_personal_data: false,
+            hide_cancelled_events: false,
             month_view_format: "%B %Y".into(),
             week_view_format: "%B %Y".into(),
             day_view_format: "%A, %B %-d, %Y".into(),
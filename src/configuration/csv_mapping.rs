@@ -0,0 +1,73 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// Which columns of a CSV calendar source hold the details of each event
+///
+/// Columns are matched against the header row, ignoring case and surrounding whitespace.
+/// Times are read in the `timezone` of the calendar source.
+#[derive(Clone, Debug, Deserialize, Serialize, Document, PartialEq, Eq)]
+#[serde(default)]
+pub struct CsvMapping {
+    /// The column holding the event title
+    #[doku(example = "Title")]
+    pub(crate) title: String,
+
+    /// The column holding the event date
+    #[doku(example = "Date")]
+    pub(crate) date: String,
+
+    /// The column holding the start time, events without one last all day
+    #[doku(example = "Start Time")]
+    pub(crate) start_time: String,
+
+    /// The column holding the end time, an end before the start is taken to be on the next day
+    #[doku(example = "End Time")]
+    pub(crate) end_time: String,
+
+    #[doku(example = "Location")]
+    pub(crate) location: String,
+
+    #[doku(example = "URL")]
+    pub(crate) url: String,
+
+    #[doku(example = "Description")]
+    pub(crate) description: String,
+
+    /// The column holding a unique identifier for each event
+    ///
+    /// Without one, events are identified by their title, date, and start time.
+    #[doku(example = "ID")]
+    pub(crate) uid: String,
+
+    /// The format of the date column in `strftime` notation
+    #[doku(example = "%m/%d/%Y")]
+    pub(crate) date_format: String,
+
+    /// The format of the time columns in `strftime` notation
+    ///
+    /// Times like `19:00`, `19:00:00`, `7:00 PM`, and `7:00PM` are recognized if omitted
+    #[doku(example = "%H:%M")]
+    pub(crate) time_format: Option<String>,
+
+    /// The character separating columns, e.g. "\t" for tab separated exports
+    #[doku(example = ",")]
+    pub(crate) delimiter: char,
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self {
+            title: "title".into(),
+            date: "date".into(),
+            start_time: "start time".into(),
+            end_time: "end time".into(),
+            location: "location".into(),
+            url: "url".into(),
+            description: "description".into(),
+            uid: "uid".into(),
+            date_format: "%Y-%m-%d".into(),
+            time_format: None,
+            delimiter: ',',
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// Where a JSON calendar source keeps its events and their details
+///
+/// All locations are JSON pointers as defined by RFC 6901, e.g. `/venue/name`.
+/// Event details are looked up relative to each event and missing details are left empty.
+#[derive(Clone, Debug, Deserialize, Serialize, Document, PartialEq, Eq)]
+#[serde(default)]
+pub struct JsonMapping {
+    /// The array of events within the document, an empty pointer is the whole document
+    #[doku(example = "/data/events")]
+    pub(crate) events: String,
+
+    /// A unique identifier for the event
+    ///
+    /// Without one, events are identified by their title and start.
+    #[doku(example = "/id")]
+    pub(crate) uid: String,
+
+    #[doku(example = "/name")]
+    pub(crate) title: String,
+
+    #[doku(example = "/summary")]
+    pub(crate) description: String,
+
+    /// The start as a date, a date-time, or a Unix timestamp in seconds
+    #[doku(example = "/starts_at")]
+    pub(crate) start: String,
+
+    /// The end as a date, a date-time, or a Unix timestamp in seconds
+    #[doku(example = "/ends_at")]
+    pub(crate) end: String,
+
+    /// The timezone of start and end times without a UTC offset
+    ///
+    /// Times without an offset or timezone are read in the `timezone` of the calendar source.
+    #[doku(example = "/timezone")]
+    pub(crate) timezone: String,
+
+    #[doku(example = "/venue/name")]
+    pub(crate) location: String,
+
+    #[doku(example = "/link")]
+    pub(crate) url: String,
+
+    /// A single category or an array of them
+    #[doku(example = "/tags")]
+    pub(crate) categories: String,
+}
+
+impl Default for JsonMapping {
+    fn default() -> Self {
+        Self {
+            events: "".into(),
+            uid: "/id".into(),
+            title: "/title".into(),
+            description: "/description".into(),
+            start: "/start".into(),
+            end: "/end".into(),
+            timezone: "/timezone".into(),
+            location: "/location".into(),
+            url: "/url".into(),
+            categories: "/categories".into(),
+        }
+    }
+}
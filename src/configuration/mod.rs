@@ -1,4 +1,7 @@
+pub mod caldav_query;
 pub mod calendar_source_config;
 pub mod config;
+pub mod csv_mapping;
+pub mod json_mapping;
 pub mod options;
 pub mod types;
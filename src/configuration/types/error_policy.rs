@@ -0,0 +1,12 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// What to do when an event in a calendar source cannot be parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Document)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ErrorPolicy {
+    /// Skip the broken event, keep the rest of the calendar, and report the event in the build summary
+    Skip,
+    /// Drop the whole calendar source
+    Fail,
+}
@@ -3,3 +3,6 @@ pub mod calendar_view;
 pub mod config_color;
 pub mod config_time_zone;
 pub mod config_url;
+pub mod error_policy;
+pub mod private_event_policy;
+pub mod source_type;
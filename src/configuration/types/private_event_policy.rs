@@ -0,0 +1,15 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// How to render events marked `CLASS:PRIVATE` or `CLASS:CONFIDENTIAL`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PrivateEventPolicy {
+    /// Render private events like any other event
+    #[default]
+    Show,
+    /// Render private events as a "Busy" block without any details
+    BusyOnly,
+    /// Leave private events out entirely
+    Hide,
+}
@@ -0,0 +1,34 @@
+use doku::Document;
+use serde::{Deserialize, Serialize};
+
+/// The format of a calendar source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SourceType {
+    /// An iCalendar file or feed, or a directory or glob pattern of them
+    #[default]
+    Ics,
+    /// A directory of Markdown files with one event per file
+    ///
+    /// The TOML (`+++`) or YAML (`---`) front matter of each file holds the event details
+    /// and the body of the file becomes the event description.
+    Markdown,
+    /// A CSV file or feed with one event per row, such as a spreadsheet export
+    ///
+    /// The columns are mapped onto event details by the `csv` setting of the source.
+    Csv,
+    /// A JSON file or API endpoint holding an array of events
+    ///
+    /// The events and their details are located by the `json` setting of the source.
+    Json,
+    /// A web page holding schema.org `Event` JSON-LD or h-event microformats
+    ///
+    /// Every event found in the page becomes an event of the calendar.
+    Html,
+    /// A CalDAV calendar collection, such as a Nextcloud or Radicale calendar
+    ///
+    /// The events within the time range of the `caldav` setting of the source are requested with a
+    /// calendar-query REPORT.
+    #[serde(rename = "caldav")]
+    CalDav,
+}
@@ -8,11 +8,3 @@ pub mod configuration;
 pub mod model;
 pub mod util;
 pub mod views;
-
-#[cfg(test)]
-mod tests {
-    use indoc::indoc;
-    use pretty_assertions::{assert_eq, assert_ne};
-
-    use super::*;
-}
@@ -90,6 +90,7 @@ Full Help Text
 
         log::info!("final debug output");
         calendar_collection.print_unparsed_properties();
+        calendar_collection.print_rejected_events();
     }
 
     Ok(())
@@ -50,7 +50,7 @@ impl Agenda {
         Agenda { events }
     }
 
-    pub(crate) fn pages(&self) -> Vec<Option<(&isize, &EventSlice)>> {
+    pub(crate) fn pages(&self) -> Vec<Option<(&isize, &EventSlice<'_>)>> {
         // chain a None to the list of agenda blocks and a None at the end
         // this will allow us to traverse the list as windows with the first and last
         // having None as appropriate
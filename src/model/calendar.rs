@@ -1,17 +1,26 @@
 use chrono::{DateTime, Months, TimeZone, Utc};
 use chrono_tz::Tz as ChronoTz;
 use color_eyre::eyre::{Context, Result};
-use ical::parser::ical::component::IcalCalendar;
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
 use ical::IcalParser;
 use indent::indent_all_by;
+use itertools::Itertools;
 use log::debug;
 use rrule::Tz as RruleTz;
 use std::io::BufRead;
+use std::iter;
 use std::rc::Rc;
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use super::event::{EventList, UnparsedProperties};
-use crate::configuration::calendar_source_config::CalendarSourceConfig;
+use super::sources::{jcal, xcal, CalendarFormat, FailedItem};
+use super::timezone::TimeZoneResolver;
+use crate::configuration::{
+    calendar_source_config::CalendarSourceConfig, config::Config, types::error_policy::ErrorPolicy,
+};
 use crate::model::event::Event;
 
 const START_DATETIME_FORMAT: &str = "%a %B %d, %Y";
@@ -31,6 +40,14 @@ pub struct Calendar {
     pub(crate) end: DateTime<Utc>,
     events: EventList,
     recurring_events: EventList,
+    /// Events carrying a `RECURRENCE-ID`, keyed by the UID of the series they modify
+    overrides: HashMap<String, EventList>,
+    /// Events which could not be parsed and were skipped
+    rejected_events: Vec<RejectedEvent>,
+    /// Recurrences after this time are not expanded
+    recurrence_end: Option<DateTime<Utc>>,
+    /// The maximum number of instances to expand for each recurring event
+    max_recurrences: u16,
     unparsed_properties: UnparsedProperties,
 }
 
@@ -60,18 +77,20 @@ impl Calendar {
     pub fn new(
         calendar: &IcalCalendar,
         source_config: Rc<CalendarSourceConfig>,
+        config: &Config,
     ) -> Result<Calendar> {
         // eprintln!("Parsing calendar: {:#?}", calendar);
         let mut name = None;
         let mut description = None;
+        let mut unparsed_properties: UnparsedProperties = HashSet::new();
 
         for property in &calendar.properties {
             match property.name.as_str() {
                 "X-WR-CALNAME" => name = property.value.clone(),
                 "X-WR-CALDESC" => description = property.value.clone(),
                 _ => {
-                    // TODO collect the unparsed properties
-                    // eprintln!("  Ignoring {}: {:?}", property.name, property.value);
+                    log::trace!("adding unparsed calendar property: {}", property.name);
+                    unparsed_properties.insert(property.name.clone());
                 }
             }
         }
@@ -83,36 +102,119 @@ impl Calendar {
             .title
             .clone()
             .or(name.clone())
+            .or(source_config.fallback_title.clone())
             .unwrap_or("No Calendar Name Found".to_owned());
 
-        let mut unparsed_properties: UnparsedProperties = HashSet::new();
+        // events display the title of their calendar, so resolve it into the config they share
+        let source_config = if source_config.title.is_none() {
+            Rc::new(CalendarSourceConfig {
+                title: Some(title.clone()),
+                ..(*source_config).clone()
+            })
+        } else {
+            source_config
+        };
+
         let mut events: EventList = Vec::new();
         let mut recurring_events: EventList = Vec::new();
+        let mut overrides: HashMap<String, EventList> = HashMap::new();
 
         // setup default start and end of calendar
         let mut start = now;
         let mut end = now + Months::new(1);
 
+        // floating times are wall-clock times in the calendar's own timezone
+        let timezones = TimeZoneResolver::new(
+            &calendar.timezones,
+            source_config
+                .timezone
+                .unwrap_or(config.display_timezone)
+                .into(),
+            config
+                .fallback_timezone
+                .unwrap_or(config.display_timezone)
+                .into(),
+        );
+
+        let on_error = source_config.on_error(config);
+
+        // the recurrence horizon is counted from the calendar's today date
+        let today = config
+            .today_date
+            .get()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| Utc.from_utc_datetime(&d))
+            .unwrap_or(now);
+        let recurrence_end = source_config
+            .recurrence_horizon_duration
+            .get()
+            .copied()
+            .flatten()
+            .map(|horizon| today + horizon);
+        let max_recurrences = source_config
+            .max_recurrences
+            .unwrap_or(config.max_recurrences);
+        let mut rejected_events = Vec::new();
+        // the number of events without a UID which were found for each generated UID
+        let mut generated_uids: HashMap<String, usize> = HashMap::new();
+
         log::debug!("parsing calendar events...");
         for event in &calendar.events {
-            let (new_event, event_unparsed_properties) = Event::new(event, source_config.clone())?;
-            unparsed_properties.extend(event_unparsed_properties.into_iter());
+            let parsed_event = Event::new(event, source_config.clone(), &timezones).and_then(
+                |(new_event, event_unparsed_properties)| {
+                    let rrules = new_event.rrule().wrap_err("could not parse rrule")?;
+                    Ok((new_event, event_unparsed_properties, rrules))
+                },
+            );
+            let (mut new_event, event_unparsed_properties, rrules) = match parsed_event {
+                Ok(parsed_event) => parsed_event,
+                Err(e) if on_error == ErrorPolicy::Skip => {
+                    log::warn!("skipping event in calendar {}: {:#}", source_config.name, e);
+                    rejected_events.push(RejectedEvent::new(&source_config.name, &e, event));
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).wrap_err_with(|| {
+                        format!("could not parse event in calendar {}", source_config.name)
+                    })
+                }
+            };
+            unparsed_properties.extend(event_unparsed_properties);
+
+            // identical events without a UID would otherwise share their generated UID
+            if !event.properties.iter().any(|p| p.name == "UID") {
+                let position = generated_uids
+                    .entry(new_event.uid().to_owned())
+                    .or_default();
+                *position += 1;
+                new_event.disambiguate_generated_uid(*position);
+            }
 
             // collect calendar start and end dates, we need this for rrule expansion
             start = start.min(new_event.start());
             end = end.max(new_event.end());
 
+            // set aside overrides of single recurrences so they can be applied during expansion
+            if new_event.recurrence_id().is_some() {
+                overrides
+                    .entry(new_event.uid().to_owned())
+                    .or_default()
+                    .push(Rc::new(new_event));
+                continue;
+            }
+
             // sort events into recurring and non-recurring
-            match &new_event.rrule().wrap_err("could not parse rrule")? {
+            match &rrules {
                 Some(rrules) => {
-                    // extend the calendar end to the until value of the RRule
+                    // extend the calendar end to the until value of the RRule, but no further than the horizon
                     if let Some(end_date) = rrules
                         .get_rrule()
                         .iter()
                         .filter_map(|r| r.get_until())
                         .reduce(|accum, date| accum.max(date))
                     {
-                        end = end.max(end_date.with_timezone(&Utc));
+                        let end_date = end_date.with_timezone(&Utc);
+                        end = end.max(recurrence_end.map_or(end_date, |r| end_date.min(r)));
                     }
 
                     // add event to recurring_events
@@ -126,6 +228,18 @@ impl Calendar {
             }
         }
 
+        // overrides without a matching recurring event are shown as regular events
+        let recurring_uids: HashSet<&str> = recurring_events.iter().map(|e| e.uid()).collect();
+        let orphaned_uids: Vec<String> = overrides
+            .keys()
+            .filter(|uid| !recurring_uids.contains(uid.as_str()))
+            .cloned()
+            .collect();
+        for uid in orphaned_uids {
+            log::debug!("no recurring event found for override with UID: {}", uid);
+            events.extend(overrides.remove(&uid).unwrap_or_default());
+        }
+
         debug!("calendar {:?} runs from {} to {}", name, start, end);
 
         // build the new calendar
@@ -138,6 +252,10 @@ impl Calendar {
             end,
             events,
             recurring_events,
+            overrides,
+            rejected_events,
+            recurrence_end,
+            max_recurrences,
             unparsed_properties,
         })
     }
@@ -156,29 +274,129 @@ impl Calendar {
             rrule::Tz::UTC.from_utc_datetime(&cal_start.naive_utc());
         // .ok_or(bail!("could not get local start time"))
         // .into();
-        let repeat_end: DateTime<RruleTz> = rrule::Tz::UTC.from_utc_datetime(&cal_end.naive_utc());
+        // recurrences past the horizon are not expanded even if the calendar runs longer
+        let horizon_limited = self
+            .recurrence_end
+            .is_some_and(|r| r.naive_utc() < cal_end.naive_utc());
+        let repeat_end: DateTime<RruleTz> = rrule::Tz::UTC.from_utc_datetime(
+            &self.recurrence_end.map_or(cal_end.naive_utc(), |r| {
+                r.naive_utc().min(cal_end.naive_utc())
+            }),
+        );
         // .single()
         // .ok_or(bail!("could not get local end time"));
 
         let mut new_events: EventList = Vec::new();
+        let no_overrides: EventList = Vec::new();
 
         for event in self.recurring_events() {
-            // TODO might want to make this a map based on UID
-            println!("Event with rrule found: {:#?}", event);
+            log::debug!("expanding recurring event: {}", event.uid());
+            let overrides = self.overrides.get(event.uid()).unwrap_or(&no_overrides);
+            let mut applied_overrides: Vec<&Rc<Event>> = Vec::new();
+            // every instance links back to its series
+            let mut instances: EventList = Vec::new();
+
             if let Ok(Some(rrule)) = event.rrule() {
+                // a series is cut short by the horizon if it has instances after it within the calendar
+                let truncated_by_horizon = horizon_limited
+                    && !rrule
+                        .clone()
+                        .after(repeat_end + chrono::Duration::seconds(1))
+                        .before(rrule::Tz::UTC.from_utc_datetime(&cal_end.naive_utc()))
+                        .all(1)
+                        .dates
+                        .is_empty();
+                let recurrences = rrule
+                    .after(repeat_start)
+                    .before(repeat_end)
+                    .all(self.max_recurrences);
+                if recurrences.limited || truncated_by_horizon {
+                    log::warn!(
+                        "recurring event {:?} in calendar {} was truncated after {} instances",
+                        event.summary(),
+                        self.source_config.name,
+                        recurrences.dates.len()
+                    );
+                }
+
                 // add event to groups
-                for recurrence_time in &rrule.after(repeat_start).before(repeat_end) {
+                for recurrence_time in recurrences.dates {
+                    let recurrence_time = recurrence_time.with_timezone(&Utc);
                     log::debug!(
                         "adding duplicate event with recurrence_time: {}",
                         recurrence_time
                     );
                     // TODO might want to push directly into the events vec and skip some of the checks in Calendar.push()
-                    new_events.push(Rc::new(
-                        // TODO ensure that we want this to be UTC here
-                        event.duplicate_with_date(recurrence_time.with_timezone(tz)),
-                    ));
+                    match find_override(overrides, recurrence_time) {
+                        // this instance was replaced outright
+                        Some(o) if o.recurrence_id() == Some(recurrence_time) => {
+                            applied_overrides.push(o);
+                            instances.push(Rc::new(o.duplicate_with_date(
+                                o.start().with_timezone(tz),
+                                recurrence_time,
+                                event,
+                            )));
+                        }
+                        // a THISANDFUTURE override shifts and modifies all later instances as well
+                        Some(o) => {
+                            // the shift is applied to the wall-clock time so it survives DST changes
+                            let event_tz = event.timezone();
+                            let original_start = o
+                                .recurrence_id()
+                                .expect("overrides always have a recurrence_id")
+                                .with_timezone(&event_tz)
+                                .naive_local();
+                            let offset =
+                                o.start().with_timezone(&event_tz).naive_local() - original_start;
+                            let shifted_start =
+                                (recurrence_time.with_timezone(&event_tz).naive_local() + offset)
+                                    .and_local_timezone(event_tz)
+                                    .earliest()
+                                    .unwrap_or_else(|| {
+                                        (recurrence_time + offset).with_timezone(&event_tz)
+                                    });
+                            instances.push(Rc::new(o.duplicate_with_date(
+                                shifted_start.with_timezone(tz),
+                                recurrence_time,
+                                event,
+                            )));
+                        }
+                        None => instances.push(Rc::new(
+                            // TODO ensure that we want this to be UTC here
+                            event.duplicate_with_date(
+                                recurrence_time.with_timezone(tz),
+                                recurrence_time,
+                                event,
+                            ),
+                        )),
+                    }
                 }
             };
+
+            // overrides whose original instance was not generated, e.g. because the instance was
+            // moved into the calendar range from outside of it, are still shown at their new time
+            for o in overrides {
+                if !applied_overrides.iter().any(|a| Rc::ptr_eq(a, o))
+                    && !o.this_and_future()
+                    && o.start() >= cal_start
+                    && o.start() <= cal_end
+                {
+                    log::debug!(
+                        "adding override without a generated instance: {:?}",
+                        o.recurrence_id()
+                    );
+                    instances.push(Rc::new(
+                        o.duplicate_with_date(
+                            o.start().with_timezone(tz),
+                            o.recurrence_id()
+                                .expect("overrides always have a recurrence_id"),
+                            event,
+                        ),
+                    ));
+                }
+            }
+
+            new_events.extend(instances);
         }
 
         // add new events to events in calendar
@@ -195,6 +413,7 @@ impl Calendar {
     pub fn parse_calendars<B>(
         buf: B,
         source_config: Rc<CalendarSourceConfig>,
+        config: &Config,
     ) -> Result<Vec<Calendar>>
     where
         B: BufRead,
@@ -204,11 +423,115 @@ impl Calendar {
         let reader = IcalParser::new(buf);
 
         for calendar in reader.flatten() {
-            calendars.push(Calendar::new(&calendar, source_config.clone())?);
+            calendars.push(Calendar::new(&calendar, source_config.clone(), config)?);
         }
         Ok(calendars)
     }
 
+    /// Parse calendar data in any of the supported formats
+    ///
+    /// jCal and xCal documents are converted into the same ical components as ICS data, so their events are identical.
+    pub(crate) fn parse_calendar_data(
+        contents: &str,
+        format: CalendarFormat,
+        source_config: Rc<CalendarSourceConfig>,
+        config: &Config,
+    ) -> Result<Vec<Calendar>> {
+        let calendars = match format {
+            CalendarFormat::Ics => {
+                return Calendar::parse_calendars(contents.as_bytes(), source_config, config)
+            }
+            CalendarFormat::Jcal => jcal::parse_calendars(contents)?,
+            CalendarFormat::Xcal => xcal::parse_calendars(contents)?,
+        };
+        calendars
+            .into_iter()
+            .map(|(calendar, failures)| {
+                Calendar::from_source(&calendar, failures, source_config.clone(), config)
+            })
+            .collect()
+    }
+
+    /// Create a calendar from events converted from a non-ICS source
+    ///
+    /// Items of the source which could not be converted are handled like events which could not be parsed.
+    pub(crate) fn from_source(
+        calendar: &IcalCalendar,
+        failures: Vec<FailedItem>,
+        source_config: Rc<CalendarSourceConfig>,
+        config: &Config,
+    ) -> Result<Calendar> {
+        let on_error = source_config.on_error(config);
+        let mut rejected_events = Vec::new();
+        for failure in failures {
+            if on_error == ErrorPolicy::Fail {
+                return Err(failure.error).wrap_err_with(|| {
+                    format!(
+                        "could not read {} in calendar {}",
+                        failure.context, source_config.name
+                    )
+                });
+            }
+            log::warn!(
+                "skipping {} in calendar {}: {:#}",
+                failure.context,
+                source_config.name,
+                failure.error
+            );
+            rejected_events.push(RejectedEvent::with_context(
+                &source_config.name,
+                &failure.error,
+                failure.context,
+            ));
+        }
+
+        let mut calendar = Calendar::new(calendar, source_config, config)?;
+        calendar.rejected_events.extend(rejected_events);
+        Ok(calendar)
+    }
+
+    /// Removes all events from the calendar for which the predicate returns false
+    ///
+    /// This must happen after recurrence expansion so that e.g. cancelled overrides still replace their series instances.
+    pub fn retain_events<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&Rc<Event>) -> bool,
+    {
+        self.events.retain(predicate);
+    }
+
+    /// Records the start times of the remaining instances of every recurring event
+    ///
+    /// This must happen after the events have been filtered so that hidden or cancelled instances are not linked.
+    pub fn record_occurrences(&self) -> Result<()> {
+        for series in self.recurring_events() {
+            series.set_occurrences(
+                self.events
+                    .iter()
+                    .filter(|e| e.series().is_some_and(|s| Rc::ptr_eq(s, series)))
+                    .map(|e| e.start())
+                    .collect(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the properties of this calendar and its events which were not parsed
+    #[must_use]
+    pub fn unparsed_properties(&self) -> &UnparsedProperties {
+        &self.unparsed_properties
+    }
+
+    #[must_use]
+    pub fn rejected_events(&self) -> &[RejectedEvent] {
+        self.rejected_events.as_ref()
+    }
+
+    #[must_use]
+    pub fn overrides(&self) -> &HashMap<String, EventList> {
+        &self.overrides
+    }
+
     #[must_use]
     pub fn start(&self) -> DateTime<Utc> {
         self.start
@@ -229,3 +552,583 @@ impl Calendar {
         self.recurring_events.as_ref()
     }
 }
+
+/// An event which was skipped because it could not be parsed
+#[derive(Debug, PartialEq, Eq)]
+pub struct RejectedEvent {
+    /// The name of the calendar source the event came from
+    pub calendar: String,
+    /// Why the event was rejected
+    pub reason: String,
+    /// The lines of the event as found in the source
+    pub context: String,
+}
+
+impl fmt::Display for RejectedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}\n{}",
+            self.calendar,
+            self.reason,
+            indent_all_by(4, &self.context)
+        )
+    }
+}
+
+impl RejectedEvent {
+    fn new(calendar: &str, reason: &color_eyre::eyre::Error, event: &IcalEvent) -> RejectedEvent {
+        RejectedEvent::with_context(calendar, reason, event_to_lines(event))
+    }
+
+    fn with_context(
+        calendar: &str,
+        reason: &color_eyre::eyre::Error,
+        context: String,
+    ) -> RejectedEvent {
+        RejectedEvent {
+            calendar: calendar.to_owned(),
+            reason: format!("{:#}", reason),
+            context,
+        }
+    }
+}
+
+/// Reconstructs the content lines of an event for error reports
+fn event_to_lines(event: &IcalEvent) -> String {
+    iter::once("BEGIN:VEVENT".to_owned())
+        .chain(event.properties.iter().map(|property| {
+            let params: String = property
+                .params
+                .iter()
+                .flatten()
+                .map(|(name, values)| format!(";{}={}", name, values.join(",")))
+                .collect();
+            format!(
+                "{}{}:{}",
+                property.name,
+                params,
+                property.value.as_deref().unwrap_or_default()
+            )
+        }))
+        .chain(iter::once("END:VEVENT".to_owned()))
+        .join("\n")
+}
+
+/// Finds the override which applies to the series instance starting at `recurrence_time`
+///
+/// An override for exactly this instance takes precedence, otherwise the latest preceding `RANGE=THISANDFUTURE` override is used.
+fn find_override(overrides: &[Rc<Event>], recurrence_time: DateTime<Utc>) -> Option<&Rc<Event>> {
+    overrides
+        .iter()
+        .find(|o| o.recurrence_id() == Some(recurrence_time))
+        .or_else(|| {
+            overrides
+                .iter()
+                .filter(|o| o.this_and_future())
+                .filter(|o| o.recurrence_id().is_some_and(|id| id < recurrence_time))
+                .max_by_key(|o| o.recurrence_id())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Timelike};
+    use chrono_tz::America::New_York;
+    use indoc::{formatdoc, indoc};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::model::sources::test_util::test_source_config;
+
+    /// Parses a calendar holding a single weekly 7pm New York event and expands it over the given month
+    fn expand_weekly_new_york_event(dt_start: &str, year: i32, month: u32) -> Calendar {
+        let ics = formatdoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VEVENT
+            UID:weekly-meeting
+            SUMMARY:Weekly Meeting
+            DTSTART;TZID=America/New_York:{dt_start}
+            DTEND;TZID=America/New_York:{dt_start}
+            RRULE:FREQ=WEEKLY;COUNT=4
+            END:VEVENT
+            END:VCALENDAR
+        "};
+        let source_config: Rc<CalendarSourceConfig> = Rc::new(
+            toml_edit::de::from_str("source = 'test.ics'\nname = 'test'\ncolor = 'red'")
+                .expect("could not parse source config"),
+        );
+        let mut calendar =
+            Calendar::parse_calendars(ics.as_bytes(), source_config, &Config::default())
+                .expect("could not parse calendar")
+                .pop()
+                .expect("no calendar found");
+
+        let month_start = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+        let cal_start = New_York.from_utc_datetime(&month_start.and_hms_opt(0, 0, 0).unwrap());
+        let cal_end = cal_start + Months::new(2);
+        calendar
+            .expand_recurrences(cal_start, cal_end, &New_York)
+            .expect("could not expand recurrences");
+        calendar
+    }
+
+    /// Parses a daily series running until 2099 and expands it from 2024-03-01 until the end of 2024
+    fn expand_long_running_series(horizon_days: i64, max_recurrences: u16) -> Calendar {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VEVENT
+            UID:daily-standup
+            SUMMARY:Daily Standup
+            DTSTART:20240301T160000Z
+            DTEND:20240301T161500Z
+            RRULE:FREQ=DAILY;UNTIL=20991231T160000Z
+            END:VEVENT
+            END:VCALENDAR
+        "};
+        let source_config: Rc<CalendarSourceConfig> = Rc::new(
+            toml_edit::de::from_str(&format!(
+                "source = 'test.ics'\nname = 'test'\ncolor = 'red'\nmax_recurrences = {}",
+                max_recurrences
+            ))
+            .expect("could not parse source config"),
+        );
+        source_config
+            .recurrence_horizon_duration
+            .set(Some(chrono::Duration::days(horizon_days)))
+            .unwrap();
+        let config = Config {
+            calendar_today_date: "2024-03-01".into(),
+            ..Default::default()
+        };
+        config
+            .today_date
+            .set(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())
+            .unwrap();
+        let mut calendar = Calendar::parse_calendars(ics.as_bytes(), source_config, &config)
+            .expect("could not parse calendar")
+            .pop()
+            .expect("no calendar found");
+
+        let cal_start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let cal_end = Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap();
+        calendar
+            .expand_recurrences(
+                cal_start.with_timezone(&New_York),
+                cal_end.with_timezone(&New_York),
+                &New_York,
+            )
+            .expect("could not expand recurrences");
+        calendar
+    }
+
+    #[test]
+    fn long_running_series_are_limited_by_cap() {
+        let calendar = expand_long_running_series(90, 10);
+        assert_eq!(calendar.events().len(), 10);
+    }
+
+    #[test]
+    fn long_running_series_are_limited_by_horizon() {
+        let calendar = expand_long_running_series(30, 1000);
+
+        // the horizon ends on 2024-03-31 at midnight, before that day's standup
+        assert_eq!(calendar.events().len(), 30);
+        assert_eq!(
+            calendar.events().iter().map(|e| e.start()).max(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 30, 16, 0, 0).unwrap())
+        );
+    }
+
+    fn local_hours_and_utc_hours(calendar: &Calendar) -> (Vec<u32>, Vec<u32>) {
+        let mut events = calendar.events().to_vec();
+        events.sort();
+        events
+            .iter()
+            .map(|e| (e.start().with_timezone(&New_York).hour(), e.start().hour()))
+            .unzip()
+    }
+
+    #[test]
+    fn recurrences_keep_wall_clock_time_across_march_transition() {
+        // DST starts on 2024-03-10 in New York
+        let calendar = expand_weekly_new_york_event("20240301T190000", 2024, 2);
+        let (local_hours, utc_hours) = local_hours_and_utc_hours(&calendar);
+
+        assert_eq!(local_hours, vec![19, 19, 19, 19]);
+        assert_eq!(
+            utc_hours,
+            vec![0, 0, 23, 23],
+            "UTC offset should change after DST starts"
+        );
+    }
+
+    #[test]
+    fn recurrences_keep_wall_clock_time_across_november_transition() {
+        // DST ends on 2024-11-03 in New York
+        let calendar = expand_weekly_new_york_event("20241025T190000", 2024, 10);
+        let (local_hours, utc_hours) = local_hours_and_utc_hours(&calendar);
+
+        assert_eq!(local_hours, vec![19, 19, 19, 19]);
+        assert_eq!(
+            utc_hours,
+            vec![23, 23, 0, 0],
+            "UTC offset should change after DST ends"
+        );
+    }
+
+    #[test]
+    fn occurrences_leave_out_filtered_instances() {
+        let mut calendar = expand_weekly_new_york_event("20240301T190000", 2024, 2);
+        let removed = Utc.with_ymd_and_hms(2024, 3, 9, 0, 0, 0).unwrap();
+        calendar.retain_events(|e| e.start() != removed);
+        calendar
+            .record_occurrences()
+            .expect("could not record occurrences");
+
+        let mut events = calendar.events().to_vec();
+        events.sort();
+        let series = events[0].series().expect("instance has no series");
+        assert_eq!(series.occurrences().len(), 3);
+        assert!(!series.occurrences().contains(&removed));
+        assert_eq!(events[0].next_occurrence(), Some(events[1].start()));
+        assert_eq!(events[1].previous_occurrence(), Some(events[0].start()));
+    }
+
+    /// Parses a calendar fixture and expands its recurring events over March 2024
+    fn expand_march_2024(contents: &str, format: CalendarFormat) -> Calendar {
+        let source_config: Rc<CalendarSourceConfig> = Rc::new(
+            toml_edit::de::from_str("source = 'community'\nname = 'community'\ncolor = 'red'")
+                .expect("could not parse source config"),
+        );
+        let mut calendar =
+            Calendar::parse_calendar_data(contents, format, source_config, &Config::default())
+                .expect("could not parse calendar")
+                .pop()
+                .expect("no calendar found");
+        assert!(calendar.rejected_events().is_empty());
+
+        let cal_start = New_York.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        calendar
+            .expand_recurrences(cal_start, cal_start + Months::new(1), &New_York)
+            .expect("could not expand recurrences");
+        calendar
+    }
+
+    /// The summary and local start time of each event of a calendar in order
+    fn local_instances(calendar: &Calendar) -> Vec<String> {
+        calendar
+            .events()
+            .iter()
+            .sorted_by_key(|e| (e.start(), e.summary().to_owned()))
+            .map(|e| {
+                format!(
+                    "{} {}",
+                    e.summary(),
+                    e.start_with_timezone(&New_York).format("%m-%d %H:%M")
+                )
+            })
+            .collect()
+    }
+
+    /// Parses a single calendar from ICS with the given extra source settings
+    fn parse_with_settings(ics: &str, source_settings: &str) -> Result<Calendar> {
+        let source_config: Rc<CalendarSourceConfig> = Rc::new(
+            toml_edit::de::from_str(&format!(
+                "source = 'test.ics'\nname = 'test'\ncolor = 'red'\n{}",
+                source_settings
+            ))
+            .expect("could not parse source config"),
+        );
+        Ok(
+            Calendar::parse_calendars(ics.as_bytes(), source_config, &Config::default())?
+                .pop()
+                .expect("no calendar found"),
+        )
+    }
+
+    #[test]
+    fn floating_times_use_the_source_or_display_timezone() {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VEVENT
+            UID:floating
+            SUMMARY:Floating Meetup
+            DTSTART:20240320T190000
+            END:VEVENT
+            END:VCALENDAR
+        "};
+        let start = |source_settings: &str| {
+            parse_with_settings(ics, source_settings)
+                .expect("could not parse calendar")
+                .events()[0]
+                .start()
+        };
+
+        assert_eq!(
+            start("timezone = 'America/New_York'"),
+            Utc.with_ymd_and_hms(2024, 3, 20, 23, 0, 0).unwrap()
+        );
+        let display_timezone: ChronoTz = Config::default().display_timezone.into();
+        assert_eq!(
+            start("").with_timezone(&display_timezone).naive_local(),
+            NaiveDate::from_ymd_opt(2024, 3, 20)
+                .unwrap()
+                .and_hms_opt(19, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn skips_or_fails_on_bad_events_according_to_policy() {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VEVENT
+            UID:no-start
+            SUMMARY:Missing Start
+            END:VEVENT
+            BEGIN:VEVENT
+            UID:potluck
+            SUMMARY:Potluck
+            DTSTART:20240320T010000Z
+            END:VEVENT
+            END:VCALENDAR
+        "};
+
+        let calendar =
+            parse_with_settings(ics, "on_error = 'skip'").expect("bad events should be skipped");
+        assert_eq!(calendar.events().len(), 1);
+        assert_eq!(calendar.rejected_events().len(), 1);
+        assert_eq!(
+            calendar.rejected_events()[0].reason,
+            "event has no start time"
+        );
+        assert!(calendar.rejected_events()[0]
+            .context
+            .contains("SUMMARY:Missing Start"));
+
+        assert!(parse_with_settings(ics, "on_error = 'fail'").is_err());
+    }
+
+    #[test]
+    fn events_without_uid_get_distinct_stable_uids() {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VEVENT
+            SUMMARY:Open House
+            DTSTART:20240316T170000Z
+            LOCATION:North Branch
+            END:VEVENT
+            BEGIN:VEVENT
+            SUMMARY:Open House
+            DTSTART:20240316T170000Z
+            LOCATION:South Branch
+            END:VEVENT
+            BEGIN:VEVENT
+            SUMMARY:Open House
+            DTSTART:20240316T170000Z
+            LOCATION:South Branch
+            END:VEVENT
+            END:VCALENDAR
+        "};
+        let uids = || -> Vec<String> {
+            let source_config: Rc<CalendarSourceConfig> = Rc::new(
+                toml_edit::de::from_str("source = 'test.ics'\nname = 'test'\ncolor = 'red'")
+                    .expect("could not parse source config"),
+            );
+            Calendar::parse_calendars(ics.as_bytes(), source_config, &Config::default())
+                .expect("could not parse calendar")
+                .pop()
+                .expect("no calendar found")
+                .events()
+                .iter()
+                .map(|e| e.uid().to_owned())
+                .collect()
+        };
+
+        let first = uids();
+        assert_eq!(
+            first.iter().unique().count(),
+            3,
+            "uids should be distinct: {:?}",
+            first
+        );
+        assert!(first[2].ends_with("-2@statical"));
+        assert_eq!(uids(), first, "uids should be stable between builds");
+    }
+
+    #[test]
+    fn recurrences_honor_exdate_and_rdate() {
+        let calendar = expand_march_2024(
+            include_str!("../../tests/fixtures/recurrence-dates.ics"),
+            CalendarFormat::Ics,
+        );
+
+        assert_eq!(
+            local_instances(&calendar),
+            vec![
+                "Workshop 03-02 00:00",
+                "Book Club 03-05 18:00",
+                "Book Club 03-14 18:00",
+                "Book Club 03-19 18:00",
+                "Book Club 03-26 18:00",
+                "Book Club 03-28 18:00",
+                "Workshop 03-30 00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides_replace_single_instances_and_later_ranges() {
+        let calendar = expand_march_2024(
+            include_str!("../../tests/fixtures/recurrence-overrides.ics"),
+            CalendarFormat::Ics,
+        );
+
+        assert_eq!(
+            local_instances(&calendar),
+            vec![
+                "Choir 03-04 18:00",
+                "Rehearsal 03-07 19:00",
+                "Choir 03-11 18:00",
+                "Rehearsal (moved) 03-15 20:00",
+                "Choir (new time) 03-18 19:00",
+                "Rehearsal 03-21 19:00",
+                "Choir (new time) 03-25 19:00",
+                "Rehearsal 03-28 19:00",
+            ]
+        );
+    }
+
+    /// Parses a calendar fixture and describes each of its events after expanding March 2024
+    fn describe_fixture_events(contents: &str, format: CalendarFormat) -> Vec<String> {
+        expand_march_2024(contents, format)
+            .events()
+            .iter()
+            .sorted_by_key(|e| (e.start(), e.summary().to_owned()))
+            .map(|e| {
+                format!(
+                    "{} {:?} {} - {} all day: {} tz: {} {:?} {:?} {:?} {:?} {} {:?}",
+                    e.uid(),
+                    e.summary(),
+                    e.start(),
+                    e.end(),
+                    e.all_day(),
+                    e.timezone(),
+                    e.description(),
+                    e.location(),
+                    e.categories(),
+                    e.geo(),
+                    e.url(),
+                    e.recurrence_description(&New_York),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jcal_and_xcal_match_ics() {
+        let ics = describe_fixture_events(
+            include_str!("../../tests/fixtures/community.ics"),
+            CalendarFormat::Ics,
+        );
+        // the expanded rehearsals, the override, the potluck and the cleanup day
+        assert_eq!(ics.len(), 9);
+
+        assert_eq!(
+            describe_fixture_events(
+                include_str!("../../tests/fixtures/community.json"),
+                CalendarFormat::Jcal,
+            ),
+            ics,
+            "jCal events should match the ICS events"
+        );
+        assert_eq!(
+            describe_fixture_events(
+                include_str!("../../tests/fixtures/community.xml"),
+                CalendarFormat::Xcal,
+            ),
+            ics,
+            "xCal events should match the ICS events"
+        );
+    }
+
+    #[test]
+    fn skips_or_fails_on_malformed_jcal_and_xcal_events() {
+        let jcal = r#"["vcalendar", [], [
+            ["vevent", [
+                ["uid", {}, "text", "potluck"],
+                ["summary", {}, "text", "Spring Potluck"],
+                ["dtstart", {}, "date-time", "2024-03-20T18:00:00Z"]
+            ], []],
+            ["vevent", [["uid", {}, "text", "broken"], ["summary", "Broken"]], []]
+        ]]"#;
+        let xcal = indoc! {r#"
+            <icalendar xmlns="urn:ietf:params:xml:ns:icalendar-2.0">
+              <vcalendar>
+                <components>
+                  <vevent>
+                    <properties>
+                      <uid><text>potluck</text></uid>
+                      <summary><text>Spring Potluck</text></summary>
+                      <dtstart><date-time>2024-03-20T18:00:00Z</date-time></dtstart>
+                    </properties>
+                  </vevent>
+                  <vevent>
+                    <properties>
+                      <uid><text>broken</text></uid>
+                      <summary/>
+                    </properties>
+                  </vevent>
+                </components>
+              </vcalendar>
+            </icalendar>
+        "#};
+        let parse = |contents: &str, format: CalendarFormat, settings: &str| {
+            Calendar::parse_calendar_data(
+                contents,
+                format,
+                test_source_config("test", "test.ics", settings),
+                &Config::default(),
+            )
+        };
+
+        for (contents, format) in [(jcal, CalendarFormat::Jcal), (xcal, CalendarFormat::Xcal)] {
+            let calendar = parse(contents, format, "on_error = 'skip'")
+                .expect("malformed event was not skipped")
+                .pop()
+                .expect("no calendar found");
+            assert_eq!(calendar.events().len(), 1, "{:?}", format);
+            assert_eq!(calendar.events()[0].summary(), "Spring Potluck");
+            assert_eq!(calendar.rejected_events().len(), 1, "{:?}", format);
+
+            let error = parse(contents, format, "on_error = 'fail'")
+                .expect_err("malformed event did not fail the calendar");
+            assert!(
+                format!("{:#}", error).contains("event broken"),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn detects_calendar_formats() {
+        assert_eq!(
+            CalendarFormat::from_extension("feeds/community.jcal"),
+            Some(CalendarFormat::Jcal)
+        );
+        assert_eq!(
+            CalendarFormat::from_content_type("application/calendar+xml; charset=utf-8"),
+            Some(CalendarFormat::Xcal)
+        );
+        assert_eq!(
+            CalendarFormat::detect(None, "\n  [\"vcalendar\", [], []]"),
+            CalendarFormat::Jcal
+        );
+        assert_eq!(
+            CalendarFormat::detect(None, "BEGIN:VCALENDAR\nEND:VCALENDAR\n"),
+            CalendarFormat::Ics
+        );
+    }
+}
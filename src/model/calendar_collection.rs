@@ -29,6 +29,7 @@ use super::event::{Event, EventList, UnparsedProperties};
 use super::week::Week;
 use crate::util::delete_dir_contents;
 use crate::views::agenda_view::AgendaView;
+use crate::views::category_view::{self, CategoryView};
 use crate::views::day_view::DayView;
 use crate::views::event_view::EventView;
 use crate::views::month_view::MonthView;
@@ -37,7 +38,10 @@ use crate::{
     configuration::{config::Config, types::calendar_view::CalendarView},
     views::feed_view::FeedView,
 };
-use crate::{model::calendar::Calendar, views::feed_view};
+use crate::{
+    model::calendar::{Calendar, RejectedEvent},
+    views::feed_view,
+};
 
 /// Type alias representing a specific day in time
 pub(crate) type LocalDay = DateTime<ChronoTz>;
@@ -116,13 +120,12 @@ impl CalendarCollection {
             CalendarView::Event => (config.render_event, "event"),
             CalendarView::Agenda => (config.render_agenda, "agenda"),
         };
-        match view_and_name {
-            (false, view_name) => bail!(
+        if let (false, view_name) = view_and_name {
+            bail!(
                 "default_view is set to {} and render_{} is set to false",
                 view_name,
                 view_name
-            ),
-            (true, _) => (),
+            )
         }
 
         let (mut calendars, unparsed_properties) = load_calendars(&config)?;
@@ -134,10 +137,24 @@ impl CalendarCollection {
         // expand recurring events
         expand_recurring_events(&mut calendars, &cal_start, &cal_end, &config)?;
 
+        for calendar in &mut calendars {
+            calendar.retain_events(|e| {
+                !(e.is_hidden() || config.hide_cancelled_events && e.is_cancelled())
+            });
+            calendar.record_occurrences()?;
+        }
+
         println!("Read {} calendars:", &calendars.len());
         for calendar in &calendars {
             println!("  Calendar: {}", calendar);
         }
+        let rejected_count: usize = calendars.iter().map(|c| c.rejected_events().len()).sum();
+        if rejected_count > 0 {
+            println!(
+                "Skipped {} events which could not be parsed (see below)",
+                rejected_count
+            );
+        }
 
         let events_by_day = group_events_by_day(&calendars, &config);
 
@@ -167,11 +184,30 @@ impl CalendarCollection {
             "The following {} properties were present but have not been parsed:",
             self.unparsed_properties.len()
         );
-        for property in &self.unparsed_properties {
+        for property in self.unparsed_properties.iter().sorted() {
             println!("  {}", property);
         }
     }
 
+    /// Returns the events which were skipped because they could not be parsed
+    pub fn rejected_events(&self) -> impl Iterator<Item = &RejectedEvent> {
+        self.calendars.iter().flat_map(|c| c.rejected_events())
+    }
+
+    pub fn print_rejected_events(&self) {
+        let rejected_events: Vec<&RejectedEvent> = self.rejected_events().collect();
+        if rejected_events.is_empty() {
+            return;
+        }
+        println!(
+            "The following {} events could not be parsed and were skipped:",
+            rejected_events.len()
+        );
+        for rejected_event in rejected_events {
+            println!("  {}", rejected_event);
+        }
+    }
+
     pub(crate) fn today_date(&self) -> NaiveDate {
         *self
             .config
@@ -216,6 +252,7 @@ impl CalendarCollection {
         context.insert("render_event", &self.config.render_event);
         context.insert("render_agenda", &self.config.render_agenda);
         context.insert("render_feed", &self.config.render_feed);
+        context.insert("render_categories", &self.config.render_categories);
 
         // TODO: convert these to functions of each view class
         let base_url_path: unix_path::PathBuf = self.config.base_url_path.path_buf().clone();
@@ -225,12 +262,16 @@ impl CalendarCollection {
         context.insert("event_view_path", &base_url_path.join("event"));
         context.insert("agenda_view_path", &base_url_path.join("agenda"));
         context.insert("feed_view_path", &base_url_path.join(feed_view::VIEW_PATH));
+        context.insert(
+            "category_view_path",
+            &base_url_path.join(category_view::VIEW_PATH),
+        );
 
         context
     }
 
     /// Returns the weeks to show of this [`CalendarCollection`].
-    pub fn weeks_to_show(&self) -> Result<Vec<Option<Week>>> {
+    pub fn weeks_to_show(&self) -> Result<Vec<Option<Week<'_>>>> {
         // Create a DateRule to iterate over all of the weeks this calendar should display
 
         // get the first week starting on the configured start of month day
@@ -268,12 +309,43 @@ impl CalendarCollection {
         // this will allow us to traverse the list as windows with the first and last
         // having None as appropriate
         let chained_iter = iter::once(None)
-            .chain(self.events().map(|e| Some(e.clone())))
+            .chain(self.event_pages().into_iter().map(Some))
             .chain(iter::once(None));
 
         Ok(chained_iter.collect())
     }
 
+    /// Returns the events which get their own event page
+    ///
+    /// If `render_series_pages` is set, the occurrences of a recurring event are replaced by their series.
+    fn event_pages(&self) -> Vec<Rc<Event>> {
+        let mut seen_series = HashSet::new();
+        let mut pages = Vec::new();
+        for event in self.events() {
+            match event.series() {
+                Some(series) if self.config.render_series_pages => {
+                    if seen_series.insert(Rc::as_ptr(series)) {
+                        pages.push(series.clone());
+                    }
+                }
+                _ => pages.push(event.clone()),
+            }
+        }
+        pages
+    }
+
+    /// Returns the occurrences of a recurring event which start on or after the today date
+    pub(crate) fn upcoming_occurrences(&self, series: &Rc<Event>) -> Vec<Rc<Event>> {
+        self.events()
+            .filter(|e| e.series().is_some_and(|s| Rc::ptr_eq(s, series)))
+            .filter(|e| {
+                e.start_with_timezone(self.display_timezone()).date_naive() >= self.today_date()
+            })
+            .sorted()
+            .cloned()
+            .collect()
+    }
+
     pub fn days_to_show(&self) -> Result<Vec<Option<Day>>> {
         let days_iterator = DateRule::daily(self.cal_start).with_end(self.cal_end);
         let mut days_to_show: Vec<Option<Day>> = vec![];
@@ -438,6 +510,10 @@ impl CalendarCollection {
             FeedView::new(self).create_view_files()?;
         };
 
+        if self.config.render_categories {
+            CategoryView::new(self).create_html_pages()?;
+        };
+
         Ok(())
     }
 
@@ -568,7 +644,7 @@ fn expand_recurring_events(
 #[must_use = "the loaded calendars must be stored somewhere"]
 fn load_calendars(config: &Config) -> Result<(Vec<Calendar>, HashSet<String>)> {
     let mut calendars = Vec::new();
-    let unparsed_properties = HashSet::new();
+    let mut unparsed_properties = HashSet::new();
 
     // convert the CalendarSourceConfigs into Result<CalendarSources>
     debug!("configuring calendar sources...");
@@ -612,6 +688,9 @@ fn load_calendars(config: &Config) -> Result<(Vec<Calendar>, HashSet<String>)> {
         debug!("parsing calendar source: {:?}", source);
         match source.parse_calendars(config) {
             Ok(mut parsed_calendars) => {
+                for calendar in &parsed_calendars {
+                    unparsed_properties.extend(calendar.unparsed_properties().iter().cloned());
+                }
                 calendars.append(&mut parsed_calendars);
             }
             Err(e) => {
@@ -717,3 +796,188 @@ fn load_templates(config: &Config) -> Result<Tera, eyre::Error> {
 
     Ok(tera)
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::configuration::types::config_time_zone::ConfigTimeZone;
+    use crate::model::sources::test_util::test_source_config;
+
+    /// Loads a fixture for March 2024 into a collection writing to a temporary directory
+    ///
+    /// All other settings are taken from `settings`.
+    fn march_2024_collection(
+        test_name: &str,
+        fixture: &str,
+        settings: Config,
+    ) -> CalendarCollection {
+        let base_dir = std::env::temp_dir().join(format!(
+            "statical-{}-test-{}",
+            test_name,
+            std::process::id()
+        ));
+        create_dir_all(base_dir.join("templates")).expect("could not create templates dir");
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(fixture);
+        let config = Config {
+            base_dir,
+            calendar_today_date: "March 1, 2024".into(),
+            calendar_start_date: Some("March 1, 2024".into()),
+            calendar_end_date: Some("March 31, 2024".into()),
+            display_timezone: ConfigTimeZone(chrono_tz::America::New_York),
+            calendar_sources: vec![test_source_config("test", &source.to_string_lossy(), "")],
+            ..settings
+        };
+        CalendarCollection::new(config).expect("could not create calendar collection")
+    }
+
+    fn overrides_collection(test_name: &str, render_series_pages: bool) -> CalendarCollection {
+        march_2024_collection(
+            test_name,
+            "recurrence-overrides.ics",
+            Config {
+                render_series_pages,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn page_summaries(collection: &CalendarCollection) -> Vec<String> {
+        collection
+            .event_pages()
+            .iter()
+            .map(|e| e.summary().to_owned())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn series_pages_replace_their_occurrences() {
+        let instances = overrides_collection("instance-pages", false);
+        let series = overrides_collection("series-pages", true);
+        fs::remove_dir_all(instances.base_dir()).ok();
+        fs::remove_dir_all(series.base_dir()).ok();
+
+        assert_eq!(
+            page_summaries(&instances),
+            vec![
+                "Choir",
+                "Choir",
+                "Choir (new time)",
+                "Choir (new time)",
+                "Rehearsal",
+                "Rehearsal",
+                "Rehearsal",
+                "Rehearsal (moved)",
+            ]
+        );
+        assert_eq!(page_summaries(&series), vec!["Choir", "Rehearsal"]);
+    }
+
+    /// Writes the event pages of a fixture and returns the upcoming dates listed on the page of a series
+    fn listed_occurrences(test_name: &str, fixture: &str, summary: &str) -> usize {
+        let collection = march_2024_collection(
+            test_name,
+            fixture,
+            Config {
+                render_series_pages: true,
+                ..Default::default()
+            },
+        );
+        EventView::new(&collection)
+            .create_html_pages()
+            .expect("could not write event pages");
+        let series = collection
+            .event_pages()
+            .into_iter()
+            .find(|e| e.summary() == summary)
+            .expect("no series page");
+        let page = fs::read_to_string(
+            collection
+                .base_dir()
+                .join(&collection.config.output_dir)
+                .join(crate::views::event_view::VIEW_PATH)
+                .join(series.file_name()),
+        )
+        .expect("could not read series page");
+        fs::remove_dir_all(collection.base_dir()).ok();
+
+        let (_, occurrences) = page
+            .split_once("Upcoming dates")
+            .expect("series page lists no occurrences");
+        let (occurrences, _) = occurrences.split_once("</ul>").unwrap();
+        occurrences.matches("<li>").count()
+    }
+
+    #[test]
+    fn series_pages_list_their_upcoming_occurrences() {
+        assert_eq!(
+            listed_occurrences("series-output", "recurrence-overrides.ics", "Rehearsal"),
+            4
+        );
+        // a series without an RRULE still has a page listing its RDATE instances
+        assert_eq!(
+            listed_occurrences("rdate-series-output", "recurrence-dates.ics", "Workshop"),
+            2
+        );
+    }
+
+    #[test]
+    fn cancelled_events_are_hidden_only_when_configured() {
+        let summaries = |hide_cancelled_events: bool| -> Vec<String> {
+            let collection = march_2024_collection(
+                "event-status",
+                "event-status.ics",
+                Config {
+                    hide_cancelled_events,
+                    ..Default::default()
+                },
+            );
+            fs::remove_dir_all(collection.base_dir()).ok();
+            collection
+                .events()
+                .sorted()
+                .map(|e| e.summary().to_owned())
+                .collect()
+        };
+
+        assert_eq!(
+            summaries(false),
+            vec!["Board Meeting", "Garden Party", "Book Swap"]
+        );
+        assert_eq!(summaries(true), vec!["Board Meeting", "Book Swap"]);
+    }
+
+    #[test]
+    fn cancelled_and_tentative_events_are_marked_in_templates() {
+        let collection = march_2024_collection(
+            "event-status-classes",
+            "event-status.ics",
+            Config::default(),
+        );
+        fs::remove_dir_all(collection.base_dir()).ok();
+        let render = |summary: &str| -> String {
+            let event = collection
+                .events()
+                .find(|e| e.summary() == summary)
+                .expect("event not found");
+            let mut context = collection.template_context();
+            context.insert("event", &event.context(&collection.config));
+            collection
+                .tera()
+                .render("event_include.html", &context)
+                .expect("could not render event")
+        };
+
+        let cancelled = render("Garden Party");
+        assert!(cancelled.contains(r#"<div class="event test cancelled">"#));
+        assert!(cancelled.contains("<del>Garden Party</del>"));
+        let tentative = render("Book Swap");
+        assert!(tentative.contains(r#"<div class="event test tentative">"#));
+        assert!(tentative.contains(r#"<span class="status">(Tentative)</span>"#));
+        assert!(render("Board Meeting").contains(r#"<div class="event test confirmed">"#));
+    }
+}
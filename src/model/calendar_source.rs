@@ -1,28 +1,52 @@
-use chrono::Duration;
+use chrono::{Duration, TimeZone, Utc};
 use color_eyre::eyre::{bail, eyre, Context, Result};
-use log::debug;
-use reqwest::header::{HeaderMap, HeaderValue, COOKIE};
+use humantime::parse_duration;
+use log::{debug, warn};
+use reqwest::{
+    blocking::Client,
+    header::{
+        HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        LAST_MODIFIED,
+    },
+    Method, StatusCode,
+};
+use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, create_dir_all, File},
-    io::{BufReader, Read, Write},
+    io::{Read, Write},
     path::{Path, PathBuf},
     rc::Rc,
+    time::SystemTime,
 };
 use url::Url;
 
 use crate::{
     configuration::{
-        calendar_source_config::CalendarSourceConfig, config::Config, types::cache_mode::CacheMode,
+        calendar_source_config::CalendarSourceConfig,
+        config::Config,
+        types::{cache_mode::CacheMode, error_policy::ErrorPolicy, source_type::SourceType},
+    },
+    model::{
+        calendar::Calendar,
+        sources::{caldav, csv, html, json, markdown, CalendarFormat},
     },
-    model::calendar::Calendar,
 };
 
 #[derive(Debug)]
 pub(crate) enum CalendarSource {
-    CalendarUrl(Url, Rc<CalendarSourceConfig>),
-    CalendarFile(PathBuf, Rc<CalendarSourceConfig>),
+    Url(Url, Rc<CalendarSourceConfig>),
+    File(PathBuf, Rc<CalendarSourceConfig>),
+    /// The files matched by a directory or glob pattern, each with its own derived config
+    Glob(Vec<(PathBuf, Rc<CalendarSourceConfig>)>),
+    /// A directory of Markdown files with one event each
+    Markdown(PathBuf, Rc<CalendarSourceConfig>),
+    /// A CalDAV calendar collection queried for the events within a time range
+    CalDav(Url, Rc<CalendarSourceConfig>),
 }
 
+/// Characters which mark a source as a glob pattern
+const GLOB_CHARACTERS: &[char] = &['*', '?', '['];
+
 impl CalendarSource {
     pub(crate) fn new(
         base_dir: &Path,
@@ -36,20 +60,75 @@ impl CalendarSource {
             .map_err(|e| eyre!(e))
             .wrap_err("could not adjust color")?;
 
+        // resolve the recurrence horizon here so that bad durations are reported as configuration errors
+        let recurrence_horizon = source_config
+            .recurrence_horizon
+            .as_ref()
+            .or(config.recurrence_horizon.as_ref())
+            .map(|horizon| {
+                Duration::from_std(parse_duration(horizon).wrap_err_with(|| {
+                    format!("could not parse recurrence_horizon: {:?}", horizon)
+                })?)
+                .wrap_err("could not convert standard duration into Chrono::Duration")
+            })
+            .transpose()?;
+        source_config
+            .recurrence_horizon_duration
+            .set(recurrence_horizon)
+            .map_err(|e| eyre!("{:?}", e))
+            .wrap_err("could not set recurrence_horizon_duration")?;
+
         log::debug!("creating calendar source: {}", source_config);
-        if let Ok(url) = Url::parse(&source_config.source) {
-            log::debug!("calendar source is a url");
-            return Ok(CalendarSource::CalendarUrl(url, source_config));
+        let path = match Url::parse(&source_config.source) {
+            // file urls are read like any other path
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map_err(|_| eyre!("could not convert file url into a path: {}", url))?,
+            Ok(url) if source_config.source_type == SourceType::CalDav => {
+                log::debug!("calendar source is a caldav collection");
+                return Ok(CalendarSource::CalDav(url, source_config));
+            }
+            Ok(url) => {
+                log::debug!("calendar source is a url");
+                return Ok(CalendarSource::Url(url, source_config));
+            }
+            Err(_) => base_dir.join(PathBuf::from(&source_config.source)),
         };
 
-        let path = base_dir.join(
-            PathBuf::try_from(&source_config.source)
-                .wrap_err("calendar source is not a valid file path")?,
-        );
+        if source_config.source_type == SourceType::CalDav {
+            bail!("caldav calendar source is not a url: {}", source_config);
+        }
+
+        if source_config.source_type == SourceType::Markdown {
+            if !path.is_dir() {
+                bail!(
+                    "markdown calendar source is not a directory: {}",
+                    source_config
+                );
+            }
+            log::debug!("calendar source is a directory of markdown files");
+            return Ok(CalendarSource::Markdown(path, source_config));
+        }
+
+        if path.is_dir() {
+            log::debug!("calendar source is a directory");
+            return Ok(CalendarSource::Glob(matched_files(
+                &path.join("*").to_string_lossy(),
+                &source_config,
+                |file| is_source_file(source_config.source_type, file),
+            )?));
+        }
 
         if path.exists() {
             log::debug!("calendar source is a file that exists");
-            Ok(CalendarSource::CalendarFile(path, source_config))
+            Ok(CalendarSource::File(path, source_config))
+        } else if source_config.source.contains(GLOB_CHARACTERS) {
+            log::debug!("calendar source is a glob pattern");
+            Ok(CalendarSource::Glob(matched_files(
+                &path.to_string_lossy(),
+                &source_config,
+                |_| true,
+            )?))
         } else {
             bail!("could not create CalendarSource from: {}", source_config);
         }
@@ -61,15 +140,52 @@ impl CalendarSource {
     pub(crate) fn parse_calendars(&self, config: &Config) -> Result<Vec<Calendar>> {
         let base_dir: &Path = &config.base_dir;
         let parsed_calendars = match self {
-            Self::CalendarFile(file, source_config) => {
+            Self::File(file, source_config) => {
                 log::info!("reading calendar file: {:?}", file);
-                let buf = BufReader::new(File::open(base_dir.join(file))?);
-                Calendar::parse_calendars(buf, source_config.clone())?
+                parse_calendar_file(&base_dir.join(file), source_config, config)?
             }
-            Self::CalendarUrl(url, source_config) => {
+            Self::Url(url, source_config) => {
                 log::info!("reading calendar url: {}", url);
-                let ics_string = retrieve_cached_url(config, source_config, url)?;
-                Calendar::parse_calendars(ics_string.as_bytes(), source_config.clone())?
+                let (contents, content_type) = retrieve_cached_url(config, source_config, url)?;
+                let format_hint = content_type
+                    .as_deref()
+                    .and_then(CalendarFormat::from_content_type)
+                    .or_else(|| CalendarFormat::from_extension(url.path()));
+                parse_source_data(&contents, format_hint, Some(url), source_config, config)?
+            }
+            Self::CalDav(url, source_config) => {
+                log::info!("querying caldav collection: {}", url);
+                let (contents, _) = retrieve_cached_url(config, source_config, url)?;
+                parse_source_data(&contents, None, Some(url), source_config, config)?
+            }
+            Self::Glob(files) => {
+                let mut calendars = Vec::new();
+                for (file, source_config) in files {
+                    log::info!("reading calendar file: {:?}", file);
+                    match parse_calendar_file(file, source_config, config) {
+                        Ok(mut parsed) => calendars.append(&mut parsed),
+                        // a single broken file should not hide all of the other calendars unless asked to
+                        Err(e) if source_config.on_error(config) == ErrorPolicy::Skip => {
+                            warn!("skipping calendar file {:?}: {:#}", file, e)
+                        }
+                        Err(e) => {
+                            return Err(e).wrap_err_with(|| {
+                                format!("could not parse calendar file {:?}", file)
+                            })
+                        }
+                    }
+                }
+                calendars
+            }
+            Self::Markdown(dir, source_config) => {
+                log::info!("reading markdown calendar directory: {:?}", dir);
+                let (calendar, failures) = markdown::read_calendar(dir, &source_config.name)?;
+                vec![Calendar::from_source(
+                    &calendar,
+                    failures,
+                    source_config.clone(),
+                    config,
+                )?]
             }
         };
 
@@ -77,17 +193,192 @@ impl CalendarSource {
     }
 }
 
+/// Parse a calendar file, detecting its format from its extension or its contents
+fn parse_calendar_file(
+    file: &Path,
+    source_config: &Rc<CalendarSourceConfig>,
+    config: &Config,
+) -> Result<Vec<Calendar>> {
+    let contents = fs::read_to_string(file)
+        .wrap_err_with(|| format!("could not read calendar file {:?}", file))?;
+    parse_source_data(
+        &contents,
+        CalendarFormat::from_extension(&file.to_string_lossy()),
+        None,
+        source_config,
+        config,
+    )
+}
+
+/// Parse the contents of a calendar file or feed according to the source type
+///
+/// The format hint is used for calendar data, whose format is detected from the contents without one.
+/// The url of a web page is needed to resolve its relative links.
+fn parse_source_data(
+    contents: &str,
+    format_hint: Option<CalendarFormat>,
+    url: Option<&Url>,
+    source_config: &Rc<CalendarSourceConfig>,
+    config: &Config,
+) -> Result<Vec<Calendar>> {
+    let (calendar, failures) = match source_config.source_type {
+        SourceType::Csv => csv::read_calendar(
+            contents,
+            &source_config.name,
+            &source_config.csv.clone().unwrap_or_default(),
+        )?,
+        SourceType::Json => json::read_calendar(
+            contents,
+            &source_config.name,
+            &source_config.json.clone().unwrap_or_default(),
+        )?,
+        SourceType::Html => html::read_calendar(contents, &source_config.name, url)?,
+        SourceType::CalDav => caldav::read_calendar(contents, &source_config.name)?,
+        SourceType::Ics | SourceType::Markdown => {
+            let format = CalendarFormat::detect(format_hint, contents);
+            debug!("reading calendar {} as {:?}", source_config.name, format);
+            return Calendar::parse_calendar_data(contents, format, source_config.clone(), config);
+        }
+    };
+
+    Ok(vec![Calendar::from_source(
+        &calendar,
+        failures,
+        source_config.clone(),
+        config,
+    )?])
+}
+
+/// Whether a file in a directory source holds data of the source type, judged by its extension
+fn is_source_file(source_type: SourceType, file: &Path) -> bool {
+    let extension = file
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match source_type {
+        SourceType::Csv => extension.as_deref() == Some("csv"),
+        SourceType::Json => extension.as_deref() == Some("json"),
+        SourceType::Html => extension.as_deref() == Some("html"),
+        SourceType::Ics | SourceType::Markdown | SourceType::CalDav => {
+            CalendarFormat::from_extension(&file.to_string_lossy()).is_some()
+        }
+    }
+}
+
+/// Find the files matching a glob pattern which are accepted by `keep` and derive a calendar config for each of them
+fn matched_files(
+    pattern: &str,
+    source_config: &CalendarSourceConfig,
+    keep: impl Fn(&Path) -> bool,
+) -> Result<Vec<(PathBuf, Rc<CalendarSourceConfig>)>> {
+    let mut files = Vec::new();
+    for entry in glob::glob(pattern)
+        .wrap_err_with(|| format!("could not parse glob pattern: {}", pattern))?
+    {
+        match entry {
+            Ok(path) if path.is_file() && keep(&path) => {
+                let file_config = source_config.for_matched_file(&path);
+                debug!("matched calendar file {:?} as {}", path, file_config.name);
+                files.push((path, Rc::new(file_config)));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("could not read matched path: {}", e),
+        }
+    }
+
+    if files.is_empty() {
+        warn!(
+            "no calendar files found for source {}",
+            source_config.source
+        );
+    }
+
+    Ok(files)
+}
+
+/// The validators of a downloaded calendar, kept next to its cache file for conditional requests
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+struct CacheValidators {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    fn from_headers(headers: &HeaderMap) -> CacheValidators {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        CacheValidators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// Read the validators of a cache file, a missing or unreadable file has none
+    fn read(path: &Path) -> Option<CacheValidators> {
+        let contents = fs::read_to_string(path).ok()?;
+        toml_edit::de::from_str(&contents)
+            .map_err(|e| warn!("could not parse cache metadata file {:?}: {}", path, e))
+            .ok()
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        if self.etag.is_none() && self.last_modified.is_none() {
+            // validators of an earlier download no longer describe the cached calendar
+            if path.exists() {
+                fs::remove_file(path).wrap_err("could not remove the cache metadata file")?;
+            }
+            return Ok(());
+        }
+        fs::write(
+            path,
+            toml_edit::ser::to_string(self).wrap_err("could not serialize cache metadata")?,
+        )
+        .wrap_err("could not write the cache metadata file")
+    }
+
+    /// Add the conditional request headers which let the server skip an unchanged calendar
+    fn add_headers(&self, headers: &mut HeaderMap) -> Result<()> {
+        if let Some(etag) = &self.etag {
+            headers.insert(
+                IF_NONE_MATCH,
+                HeaderValue::from_str(etag)
+                    .wrap_err("could not convert cached ETag into valid HeaderValue")?,
+            );
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(
+                IF_MODIFIED_SINCE,
+                HeaderValue::from_str(last_modified)
+                    .wrap_err("could not convert cached Last-Modified into valid HeaderValue")?,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the contents of a calendar url, along with its content type if it was downloaded
+///
+/// Once the cache file has expired, the calendar is only downloaded again if the server reports a change
+/// since the `ETag` or `Last-Modified` date of the cached download.
 fn retrieve_cached_url(
     config: &Config,
     source_config: &Rc<CalendarSourceConfig>,
     url: &Url,
-) -> Result<String, color_eyre::eyre::Error> {
+) -> Result<(String, Option<String>), color_eyre::eyre::Error> {
     // setup the cache directory
     // TODO: might want to do this once in the Config or CalendarCollection
     let cache_dir = &config.base_dir.join(&config.cache_dir);
     // create the cache file path
     let mut calendar_cache_file = cache_dir.join(&source_config.name);
     calendar_cache_file.set_extension("ics");
+    // the validators of the cached download are kept in a sidecar file
+    let mut validators_file = calendar_cache_file.clone();
+    validators_file.set_extension("ics.meta");
 
     if config.cache_mode != CacheMode::NeverCache {
         // make the cache directory if it does not exist
@@ -128,7 +419,7 @@ fn retrieve_cached_url(
 
                 // return the cached calendar contents
                 debug!("cache file is valid, returning cached data");
-                return Ok(file_buffer);
+                return Ok((file_buffer, None));
             }
         }
     }
@@ -149,14 +440,50 @@ fn retrieve_cached_url(
             }
         }
 
+        // ask the server to skip the download if the cached calendar is still current
+        let validators =
+            if config.cache_mode != CacheMode::NeverCache && calendar_cache_file.exists() {
+                CacheValidators::read(&validators_file)
+            } else {
+                None
+            };
+        if let Some(validators) = &validators {
+            debug!("making a conditional request with: {:?}", validators);
+            validators.add_headers(&mut headers)?;
+        }
+
         // retrieve the calendar
         debug!("downloading the calendar from: {}", url);
-        let response = reqwest::blocking::Client::new()
-            .get(url.as_ref())
-            .headers(headers)
+        let mut request = match source_config.source_type {
+            SourceType::CalDav => caldav_request(config, source_config, url)?,
+            _ => Client::new().get(url.as_ref()),
+        }
+        .headers(headers);
+        if let Some(username) = &source_config.username {
+            request = request.basic_auth(username, source_config.password.as_ref());
+        }
+        let response = request
             .send()
             .wrap_err("could not get content from downloaded calendar")?;
 
+        // an unchanged calendar is as good as a fresh cache file
+        if validators.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+            debug!("calendar has not been modified, refreshing the cache file");
+            let mut cache_file = File::options()
+                .read(true)
+                .write(true)
+                .open(&calendar_cache_file)
+                .wrap_err("could not open cache file for refresh")?;
+            cache_file
+                .set_modified(SystemTime::now())
+                .wrap_err("could not update the last modified time of cache file")?;
+            let mut file_buffer = String::new();
+            cache_file
+                .read_to_string(&mut file_buffer)
+                .wrap_err("could not read contents of cache file")?;
+            return Ok((file_buffer, None));
+        }
+
         // throw an error if we are not using the cache and we could not actually download a calendar
         if config.cache_mode == CacheMode::NeverCache || !(response.status()).is_success() {
             let status = &response.status();
@@ -167,6 +494,13 @@ fn retrieve_cached_url(
             ));
         }
 
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let validators = CacheValidators::from_headers(response.headers());
+
         // get the response body
         let ics_string = &response
             .text()
@@ -182,13 +516,346 @@ fn retrieve_cached_url(
                 .wrap_err("could not create the cache file")?
                 .write_all(ics_string.as_bytes())
                 .wrap_err("could not write the calendar to its cache file")?;
+            validators.write(&validators_file)?;
         }
 
         // return the response body
-        return Ok(ics_string.clone());
+        return Ok((ics_string.clone(), content_type));
     }
 
     Err(eyre!(
         "could not retrieve a cached file or download from the network with the current cache mode"
     ))
 }
+
+/// A calendar-query REPORT for the events of a CalDAV collection within the configured time range
+fn caldav_request(
+    config: &Config,
+    source_config: &CalendarSourceConfig,
+    url: &Url,
+) -> Result<reqwest::blocking::RequestBuilder> {
+    // the time range is counted from the calendar's today date like the recurrence horizon
+    let today = config
+        .today_date
+        .get()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| Utc.from_utc_datetime(&d))
+        .unwrap_or_else(Utc::now);
+    let (start, end) =
+        caldav::time_range(today, &source_config.caldav.clone().unwrap_or_default())?;
+    debug!("querying caldav events from {} to {}", start, end);
+
+    Ok(Client::new()
+        .request(
+            Method::from_bytes(b"REPORT").wrap_err("could not create REPORT method")?,
+            url.as_ref(),
+        )
+        .header("Depth", "1")
+        .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(caldav::calendar_query(start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::model::event::Event;
+    use crate::model::sources::test_util::test_source_config;
+
+    const PARTNER_EVENTS: &str = include_str!("../../tests/fixtures/partner-events.json");
+
+    const PARTNER_SETTINGS: &str =
+        "source_type = 'json'\non_error = 'skip'\n[json]\nevents = '/events'\ntitle = '/name'";
+
+    fn event_summaries(source: &CalendarSource, config: &Config) -> Vec<String> {
+        let calendars = source
+            .parse_calendars(config)
+            .expect("could not parse calendars");
+        assert_eq!(calendars.len(), 1);
+        assert_eq!(
+            calendars[0].rejected_events().len(),
+            1,
+            "the event without a start should be rejected"
+        );
+        calendars[0]
+            .events()
+            .iter()
+            .map(|e| e.summary().to_owned())
+            .collect()
+    }
+
+    fn matched_names(source: &CalendarSource) -> Vec<&str> {
+        match source {
+            CalendarSource::Glob(files) => files.iter().map(|(_, c)| c.name.as_str()).collect(),
+            other => panic!("expected a glob source, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_every_matching_file_of_directories_and_globs() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = Config::default();
+
+        let directory = CalendarSource::new(
+            base_dir,
+            test_source_config("club", "tests/fixtures/calendar-directory", ""),
+            &config,
+        )
+        .expect("could not create directory source");
+        assert_eq!(
+            matched_names(&directory),
+            vec!["club-board-meeting", "club-broken", "club-garden-party"]
+        );
+
+        let glob = CalendarSource::new(
+            base_dir,
+            test_source_config("club", "tests/fixtures/calendar-directory/*-*.ics", ""),
+            &config,
+        )
+        .expect("could not create glob source");
+        assert_eq!(
+            matched_names(&glob),
+            vec!["club-board-meeting", "club-garden-party"]
+        );
+        let calendars = glob
+            .parse_calendars(&config)
+            .expect("could not parse calendars");
+        assert_eq!(calendars.len(), 2);
+    }
+
+    #[test]
+    fn skips_or_fails_on_broken_matched_files_according_to_policy() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = Config::default();
+        let parse = |settings: &str| {
+            CalendarSource::new(
+                base_dir,
+                test_source_config("club", "tests/fixtures/calendar-directory/*", settings),
+                &config,
+            )
+            .expect("could not create glob source")
+            .parse_calendars(&config)
+        };
+
+        let calendars = parse("on_error = 'skip'").expect("broken file was not skipped");
+        assert_eq!(calendars.len(), 2);
+        let error = parse("on_error = 'fail'").expect_err("broken file did not fail the source");
+        assert!(format!("{:#}", error).contains("broken.xml"));
+    }
+
+    #[test]
+    fn reads_json_from_file_urls() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/partner-events.json");
+        let url = Url::from_file_path(path).expect("could not create file url");
+        let config = Config::default();
+        let source = CalendarSource::new(
+            Path::new("."),
+            test_source_config("partner", url.as_str(), PARTNER_SETTINGS),
+            &config,
+        )
+        .expect("could not create source");
+
+        assert!(matches!(source, CalendarSource::File(..)));
+        assert_eq!(
+            event_summaries(&source, &config),
+            vec!["Spring Potluck", "Park Cleanup Day"]
+        );
+    }
+
+    #[test]
+    fn reads_json_from_http_endpoints() {
+        let (url, server) = serve_once(
+            "200 OK",
+            "Content-Type: application/json\r\n",
+            PARTNER_EVENTS,
+        );
+        let url = format!("{}/api/events", url);
+
+        let base_dir =
+            std::env::temp_dir().join(format!("statical-json-test-{}", std::process::id()));
+        let config = Config {
+            base_dir: base_dir.clone(),
+            ..Default::default()
+        };
+        let source = CalendarSource::new(
+            &base_dir,
+            test_source_config("partner", &url, PARTNER_SETTINGS),
+            &config,
+        )
+        .expect("could not create source");
+        let summaries = event_summaries(&source, &config);
+        server.join().unwrap();
+        fs::remove_dir_all(base_dir).ok();
+
+        assert_eq!(summaries, vec!["Spring Potluck", "Park Cleanup Day"]);
+    }
+
+    #[test]
+    fn queries_caldav_collections() {
+        let (url, server) = serve_once(
+            "207 Multi-Status",
+            "Content-Type: application/xml; charset=utf-8\r\n",
+            include_str!("../../tests/fixtures/caldav-report.xml"),
+        );
+        let source_config = test_source_config(
+            "shared",
+            &format!("{}/remote.php/dav/calendars/club/shared/", url),
+            "source_type = 'caldav'\non_error = 'skip'\nusername = 'club'\npassword = 'secret'\n\
+             cookies = ['session=abc123']\n[caldav]\npast = '30 days'\nfuture = '60 days'",
+        );
+
+        let base_dir =
+            std::env::temp_dir().join(format!("statical-caldav-test-{}", std::process::id()));
+        let config = Config {
+            base_dir: base_dir.clone(),
+            ..Default::default()
+        };
+        config
+            .today_date
+            .set(NaiveDate::from_ymd_opt(2024, 3, 15).unwrap())
+            .unwrap();
+        let source = CalendarSource::new(&base_dir, source_config, &config)
+            .expect("could not create source");
+        assert!(matches!(source, CalendarSource::CalDav(..)));
+        let calendars = source
+            .parse_calendars(&config)
+            .expect("could not parse calendars");
+        let request = server.join().unwrap();
+        fs::remove_dir_all(base_dir).ok();
+
+        assert!(request.starts_with("REPORT /remote.php/dav/calendars/club/shared/ HTTP/1.1\r\n"));
+        let headers = request.to_ascii_lowercase();
+        assert!(headers.contains("\r\ndepth: 1\r\n"));
+        // "club:secret" in base64
+        assert!(headers.contains("\r\nauthorization: basic y2x1yjpzzwnyzxq=\r\n"));
+        assert!(headers.contains("\r\ncookie: session=abc123\r\n"));
+        assert!(
+            request.contains(r#"<C:time-range start="20240214T000000Z" end="20240514T000000Z"/>"#)
+        );
+
+        assert_eq!(calendars.len(), 1);
+        assert_eq!(calendars[0].rejected_events().len(), 1);
+        let summaries = |events: &[Rc<Event>]| -> Vec<String> {
+            events.iter().map(|e| e.summary().to_owned()).collect()
+        };
+        assert_eq!(summaries(calendars[0].events()), vec!["Board Meeting"]);
+        assert_eq!(
+            summaries(calendars[0].recurring_events()),
+            vec!["Volunteer Shift"]
+        );
+    }
+
+    #[test]
+    fn revalidates_expired_cache_files() {
+        let base_dir =
+            std::env::temp_dir().join(format!("statical-cache-test-{}", std::process::id()));
+        let config = Config {
+            base_dir: base_dir.clone(),
+            ..Default::default()
+        };
+        config
+            .cache_timeout_duration
+            .set(Duration::hours(1))
+            .unwrap();
+        let cache_file = base_dir.join(&config.cache_dir).join("partner.ics");
+
+        let (url, server) = serve_once(
+            "200 OK",
+            "Content-Type: application/json\r\nETag: \"v1\"\r\nLast-Modified: Wed, 13 Mar 2024 18:00:00 GMT\r\n",
+            PARTNER_EVENTS,
+        );
+        let source = CalendarSource::new(
+            &base_dir,
+            test_source_config("partner", &url, PARTNER_SETTINGS),
+            &config,
+        )
+        .expect("could not create source");
+        event_summaries(&source, &config);
+        let first_request = server.join().unwrap().to_ascii_lowercase();
+        assert!(!first_request.contains("if-none-match"));
+
+        // expire the cache file
+        File::options()
+            .write(true)
+            .open(&cache_file)
+            .and_then(|file| {
+                file.set_modified(SystemTime::now() - Duration::hours(2).to_std().unwrap())
+            })
+            .expect("could not expire cache file");
+
+        let (url, server) = serve_once("304 Not Modified", "ETag: \"v1\"\r\n", "");
+        let source = CalendarSource::new(
+            &base_dir,
+            test_source_config("partner", &url, PARTNER_SETTINGS),
+            &config,
+        )
+        .expect("could not create source");
+        let summaries = event_summaries(&source, &config);
+        let second_request = server.join().unwrap().to_ascii_lowercase();
+        let cache_file_age = fs::metadata(&cache_file)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default());
+        fs::remove_dir_all(base_dir).ok();
+
+        assert!(second_request.contains("\r\nif-none-match: \"v1\"\r\n"));
+        assert!(second_request.contains("\r\nif-modified-since: wed, 13 mar 2024 18:00:00 gmt\r\n"));
+        assert_eq!(summaries, vec!["Spring Potluck", "Park Cleanup Day"]);
+        assert!(
+            cache_file_age.expect("could not get cache file age")
+                < Duration::hours(1).to_std().unwrap(),
+            "the cache file should be fresh again"
+        );
+    }
+
+    /// Answer a single request with the given response and return the request it received
+    fn serve_once(
+        status: &'static str,
+        headers: &'static str,
+        body: &'static str,
+    ) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind test server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("no request received");
+            // the request has to be read before responding
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                request.push_str(&line);
+                if line.len() <= 2 {
+                    break;
+                }
+            }
+            let mut content = vec![0; content_length];
+            reader.read_exact(&mut content).unwrap();
+            request.push_str(&String::from_utf8(content).unwrap());
+
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                headers,
+                body.len(),
+                body
+            )
+            .unwrap();
+            request
+        });
+        (url, server)
+    }
+}
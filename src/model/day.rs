@@ -3,7 +3,7 @@ use chrono_tz::Tz as ChronoTz;
 use chronoutil::DateRule;
 use num_traits::FromPrimitive;
 use serde::Serialize;
-use std::{fmt, path::PathBuf, u8};
+use std::{fmt, path::PathBuf};
 
 use crate::views::{day_view, month_view, week_view};
 
@@ -1,5 +1,5 @@
 use chrono::{
-    DateTime, Datelike, Duration, IsoWeek, Month, NaiveDate, NaiveDateTime, Utc, Weekday,
+    DateTime, Datelike, Duration, IsoWeek, Month, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
 };
 use chrono_humanize::{Accuracy, HumanTime, Tense};
 use chrono_tz::Tz as ChronoTz;
@@ -9,16 +9,27 @@ use ical::parser::ical::component::IcalEvent;
 use indent::indent_all_by;
 use num_traits::FromPrimitive;
 use regex::{Regex, RegexSet};
-use rrule::RRuleSet;
+use rrule::{RRule, RRuleSet, Tz as RruleTz, Unvalidated};
 use serde::Serialize;
+use std::cell::OnceCell;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering::Relaxed;
-use std::{collections::HashSet, fmt, rc::Rc, sync::atomic::AtomicUsize};
+use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, iter,
+    rc::Rc,
+};
 use unescaper::unescape;
 
-use crate::configuration::{calendar_source_config::CalendarSourceConfig, config::Config};
+use super::recurrence::describe_rrule;
+use super::timezone::TimeZoneResolver;
+use crate::configuration::{
+    calendar_source_config::CalendarSourceConfig, config::Config,
+    types::private_event_policy::PrivateEventPolicy,
+};
+use crate::util::stable_hash;
 use crate::views::{
-    day_view,
+    category_view, day_view,
     event_view::{self},
     month_view, week_view,
 };
@@ -31,6 +42,8 @@ enum ParseType {
 
 const MISSING_SUMMARY: &str = "None";
 const MISSING_DESCRIPTION: &str = "None";
+/// The summary shown in place of redacted private events
+const BUSY_SUMMARY: &str = "Busy";
 
 // const START_DATETIME_FORMAT = format_description!(
 //     "[weekday] [month repr:long] [day], [year] at [hour repr:12]:[minute][period case:lower]"
@@ -42,13 +55,162 @@ const START_DATETIME_FORMAT: &str = "%a %B %d, %Y at %H:%M%P";
 // );
 const END_DATETIME_FORMAT: &str = "%H:%M%P";
 
-// const RRULE_DTSTART_PARSING_FORMAT = format_description!(
-//     "[year][month][day]T[hour][minute][second]Z"
-// );
-const RRULE_DTSTART_PARSING_FORMAT: &str = "%Y%m%dT%H%M%SZ";
-
 const EVENT_FILE_FORMAT: &str = "%Y-%m-%d";
 
+/// The format used to write recurrence instance times, this matches the ical UTC date-time format
+const RECURRENCE_ID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The format of the next and previous occurrence dates of a recurring event
+const OCCURRENCE_FORMAT: &str = "%a, %-d %B %Y";
+
+/// The `STATUS` of an event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    Cancelled,
+}
+
+impl FromStr for EventStatus {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "TENTATIVE" => Ok(EventStatus::Tentative),
+            "CONFIRMED" => Ok(EventStatus::Confirmed),
+            "CANCELLED" => Ok(EventStatus::Cancelled),
+            _ => bail!("unknown event status: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for EventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventStatus::Tentative => write!(f, "TENTATIVE"),
+            EventStatus::Confirmed => write!(f, "CONFIRMED"),
+            EventStatus::Cancelled => write!(f, "CANCELLED"),
+        }
+    }
+}
+
+/// The access classification given by the `CLASS` property of an event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventClass {
+    Public,
+    Private,
+    Confidential,
+}
+
+impl From<&str> for EventClass {
+    /// Unrecognized classes are treated as private as required by RFC 5545
+    fn from(value: &str) -> Self {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "PUBLIC" => EventClass::Public,
+            "CONFIDENTIAL" => EventClass::Confidential,
+            _ => EventClass::Private,
+        }
+    }
+}
+
+impl fmt::Display for EventClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventClass::Public => write!(f, "PUBLIC"),
+            EventClass::Private => write!(f, "PRIVATE"),
+            EventClass::Confidential => write!(f, "CONFIDENTIAL"),
+        }
+    }
+}
+
+/// A position given by the `GEO` property of an event
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Geo {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+// coordinates are checked to be within range when parsed, so they are never NaN
+impl Eq for Geo {}
+
+impl FromStr for Geo {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (latitude, longitude) = s.split_once(';').ok_or(eyre!(
+            "GEO value is not of the form latitude;longitude: {}",
+            s
+        ))?;
+        let latitude: f64 = latitude
+            .trim()
+            .parse()
+            .wrap_err("could not parse GEO latitude")?;
+        let longitude: f64 = longitude
+            .trim()
+            .parse()
+            .wrap_err("could not parse GEO longitude")?;
+        // the range checks also reject NaN and infinite values
+        if !(-90.0..=90.0).contains(&latitude) {
+            bail!("GEO latitude is out of range: {}", latitude);
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            bail!("GEO longitude is out of range: {}", longitude);
+        }
+        Ok(Geo {
+            latitude,
+            longitude,
+        })
+    }
+}
+
+/// A person referenced by an `ORGANIZER` or `ATTENDEE` property
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Person {
+    /// The common name (`CN` parameter)
+    pub name: Option<String>,
+    /// The email address taken from a `mailto:` value
+    pub email: Option<String>,
+}
+
+impl Person {
+    fn from_property(property: &ical::property::Property) -> Person {
+        let name = property
+            .params
+            .iter()
+            .flatten()
+            .find(|(name, _)| name == "CN")
+            .and_then(|(_, values)| values.first())
+            .map(|name| name.trim_matches('"').to_owned());
+        let email = property.value.as_deref().and_then(|value| {
+            value
+                .get(..7)
+                .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+                .map(|_| value[7..].to_owned())
+        });
+        Person { name, email }
+    }
+
+    /// Returns a copy of this person without an email address, or `None` if nothing else is known about them
+    fn redacted(&self) -> Option<Person> {
+        self.name.as_ref().map(|name| Person {
+            name: Some(name.clone()),
+            email: None,
+        })
+    }
+}
+
+/// A property which statical does not interpret itself, e.g. an `X-` extension property
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExtraProperty {
+    pub value: String,
+    pub params: BTreeMap<String, Vec<String>>,
+}
+
+/// Unparsed properties keyed by property name, a property may appear more than once
+pub type ExtraProperties = BTreeMap<String, Vec<ExtraProperty>>;
+
 pub type Year = i32;
 pub type WeekNum = u8;
 
@@ -59,20 +221,53 @@ pub type UnparsedProperties = HashSet<String>;
 /// These are reference counted since they may appear in more than one list
 pub type EventList = Vec<Rc<Event>>;
 
-/// This mostly exists to ensure that there is something to use as a unique ID for events when creating file names
-static EVENT_COUNT: AtomicUsize = AtomicUsize::new(0);
-
 #[derive(Debug, PartialEq, Eq)]
 pub struct Event {
     calendar_config: Rc<CalendarSourceConfig>,
+    /// The UID of the event, or a generated one if the event does not provide a UID
+    uid: String,
+    /// The original start time of the series instance this event overrides or was expanded from
+    recurrence_id: Option<DateTime<Utc>>,
+    /// Whether this override also applies to all later instances of the series (`RANGE=THISANDFUTURE`)
+    this_and_future: bool,
+    /// Whether the event spans whole days (`VALUE=DATE`)
+    ///
+    /// The start of an all-day event is stored as midnight UTC but is floating,
+    /// so it is never shifted into the display timezone.
+    all_day: bool,
     summary: Option<String>,
     description: Option<String>,
     start: DateTime<Utc>,
+    /// The timezone in which `DTSTART` was given
+    ///
+    /// Recurrences are expanded in this timezone so that they keep their wall-clock time across DST changes.
+    timezone: ChronoTz,
     duration: Duration,
     rrule: Option<String>,
+    /// Occurrences removed from the recurrence set via `EXDATE`
+    exdates: Vec<DateTime<Utc>>,
+    /// Occurrences added to the recurrence set via `RDATE`
+    rdates: Vec<DateTime<Utc>>,
     location: Option<String>,
+    geo: Option<Geo>,
+    organizer: Option<Person>,
+    attendees: Vec<Person>,
+    contact: Option<String>,
     url: Option<String>,
-    event_number: usize,
+    /// The address of an image for the event, from the `IMAGE` property of RFC 7986
+    image: Option<String>,
+    status: Option<EventStatus>,
+    class: Option<EventClass>,
+    /// The values of all `CATEGORIES` properties in the order they appear
+    categories: Vec<String>,
+    /// Properties which are not parsed into any of the fields above
+    extra: ExtraProperties,
+    /// The recurring event this event is an instance of
+    series: Option<Rc<Event>>,
+    /// The start times of the expanded instances of a recurring event in ascending order
+    ///
+    /// This is filled in once recurrences have been expanded.
+    occurrences: OnceCell<Vec<DateTime<Utc>>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -81,6 +276,10 @@ pub struct EventContext {
     calendar_name: String,
     calendar_title: String,
     calendar_color: String,
+    /// The UID of the event, left out for redacted events
+    uid: Option<String>,
+    recurrence_id: Option<String>,
+    all_day: bool,
     summary: String,
     description: String,
     start: String,
@@ -90,11 +289,36 @@ pub struct EventContext {
     duration: String,
     // NOTE: not sure if we want this in event context as well as day context
     iso_week: u8,
+    location: Option<String>,
+    geo: Option<Geo>,
+    organizer: Option<Person>,
+    attendees: Vec<Person>,
+    contact: Option<String>,
     url: String,
+    image: Option<String>,
+    status: Option<EventStatus>,
+    class: EventClass,
+    redacted: bool,
+    categories: Vec<CategoryContext>,
+    extra: ExtraProperties,
+    /// The `RRULE` of the series this event belongs to
+    rrule: Option<String>,
+    /// An English description of the `RRULE`, e.g. "Every 2nd Tuesday of the month"
+    recurrence: Option<String>,
+    next_occurrence: Option<String>,
+    previous_occurrence: Option<String>,
     file_path: String,
     day_view_path: String,
 }
 
+/// A category of an event as provided to templates
+#[derive(Debug, Serialize)]
+pub struct CategoryContext {
+    name: String,
+    slug: String,
+    path: String,
+}
+
 impl fmt::Display for Event {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -122,7 +346,7 @@ impl Ord for Event {
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.start.partial_cmp(&other.start)
+        Some(self.cmp(other))
     }
 }
 
@@ -131,9 +355,12 @@ impl Event {
     pub fn context(&self, config: &Config) -> EventContext {
         EventContext {
             // TODO: add an agenda_header_format to the config
-            agenda_header: self.start.format("%a, %-d %B %Y").to_string(),
+            agenda_header: self
+                .start_with_timezone(&config.display_timezone)
+                .format("%a, %-d %B %Y")
+                .to_string(),
             calendar_name: self.calendar_config.name.clone(),
-            // TODO: make sure that this is using the fallback from the calendar
+            // the calendar fills in its own title if the config does not provide one
             calendar_title: self
                 .calendar_config
                 .title
@@ -148,34 +375,88 @@ impl Event {
             } else {
                 self.calendar_config.color.to_hex_string()
             },
+            // identifiers and recurrence rules could be used to tell redacted events apart
+            uid: Some(self.uid.clone()).filter(|_| !self.is_redacted()),
+            recurrence_id: self
+                .recurrence_id
+                .filter(|_| !self.is_redacted())
+                .map(|id| id.format(RECURRENCE_ID_FORMAT).to_string()),
+            all_day: self.all_day,
             summary: self.summary().into(),
-            description: self
-                .description
-                .as_deref()
-                .unwrap_or("NO DESCRIPTION")
-                .into(),
+            description: if self.is_redacted() {
+                String::new()
+            } else {
+                self.description
+                    .as_deref()
+                    .unwrap_or("NO DESCRIPTION")
+                    .into()
+            },
             start: self
-                .start()
-                .with_timezone::<chrono_tz::Tz>(&config.display_timezone.into())
+                .start_with_timezone(&config.display_timezone)
                 .format(&config.event_start_format)
                 .to_string(),
             start_timestamp: self
-                .start()
-                .with_timezone::<chrono_tz::Tz>(&config.display_timezone.into())
+                .start_with_timezone(&config.display_timezone)
                 .timestamp(),
             end: self
-                .end()
-                .with_timezone::<chrono_tz::Tz>(&config.display_timezone.into())
+                .end_with_timezone(&config.display_timezone)
                 .format(&config.event_end_format)
                 .to_string(),
-            end_timestamp: self
-                .end()
-                .with_timezone::<chrono_tz::Tz>(&config.display_timezone.into())
-                .timestamp(),
+            end_timestamp: self.end_with_timezone(&config.display_timezone).timestamp(),
             duration: HumanTime::from(self.duration).to_text_en(Accuracy::Precise, Tense::Present),
             iso_week: self.start.iso_week().week() as u8,
+            location: self.location().map(str::to_owned),
+            geo: self.geo(),
+            // personal data may be left out for public sites
+            organizer: if config.hide_personal_data {
+                self.organizer().and_then(Person::redacted)
+            } else {
+                self.organizer().cloned()
+            },
+            attendees: if config.hide_personal_data {
+                Vec::new()
+            } else {
+                self.attendees().to_vec()
+            },
+            // contacts are free text which usually holds a name, phone number, and email address
+            contact: if config.hide_personal_data {
+                None
+            } else {
+                self.contact().map(str::to_owned)
+            },
             url: self.url().to_owned(),
-            file_path: self.file_path(),
+            image: self.image().map(str::to_owned),
+            status: self.status,
+            class: self.class(),
+            redacted: self.is_redacted(),
+            categories: self
+                .categories()
+                .iter()
+                .map(|name| CategoryContext {
+                    name: name.clone(),
+                    slug: category_view::category_slug(name),
+                    path: category_view::category_path(config, name),
+                })
+                .collect(),
+            extra: self.extra().clone(),
+            rrule: self
+                .series_rrule()
+                .filter(|_| !self.is_redacted())
+                .map(str::to_owned),
+            recurrence: self
+                .recurrence_description(&config.display_timezone.into())
+                .filter(|_| !self.is_redacted()),
+            next_occurrence: self.next_occurrence().map(|o| {
+                o.with_timezone(&ChronoTz::from(config.display_timezone))
+                    .format(OCCURRENCE_FORMAT)
+                    .to_string()
+            }),
+            previous_occurrence: self.previous_occurrence().map(|o| {
+                o.with_timezone(&ChronoTz::from(config.display_timezone))
+                    .format(OCCURRENCE_FORMAT)
+                    .to_string()
+            }),
+            file_path: self.page_path(config),
             day_view_path: self.day_view_path(),
         }
     }
@@ -185,19 +466,33 @@ impl Event {
             Regex::new("[^a-zA-Z0-9_-]+").expect("could not compile event summary replacer regex");
         replace_pattern
             .replace_all(
-                self.summary
-                    .as_ref()
-                    .unwrap_or(&format!("event-{}", self.event_number)),
+                if self.is_redacted() {
+                    BUSY_SUMMARY
+                } else {
+                    self.summary.as_deref().unwrap_or("event")
+                },
                 "_",
             )
             .to_string()
     }
 
+    /// Returns an identifier which is unique to this event (or recurrence instance) and stable between builds
+    ///
+    /// This is derived from the calendar name, the UID, and the recurrence instance time.
+    pub(crate) fn identity(&self) -> u64 {
+        let recurrence_id = self
+            .recurrence_id
+            .map(|id| id.format(RECURRENCE_ID_FORMAT).to_string())
+            .unwrap_or_default();
+        stable_hash(&[&self.calendar_config.name, &self.uid, &recurrence_id])
+    }
+
     pub fn file_name(&self) -> String {
         format!(
-            "{}-{}.html",
+            "{}-{}-{:016x}.html",
             self.start().format(EVENT_FILE_FORMAT),
-            self.summary_for_filename()
+            self.summary_for_filename(),
+            self.identity()
         )
     }
 
@@ -210,6 +505,16 @@ impl Event {
             .to_string()
     }
 
+    /// Returns the path of the event page which shows this event
+    ///
+    /// Occurrences of a recurring event share the page of their series if `render_series_pages` is set.
+    pub fn page_path(&self, config: &Config) -> String {
+        match &self.series {
+            Some(series) if config.render_series_pages => series.file_path(),
+            _ => self.file_path(),
+        }
+    }
+
     pub fn day_view_path(&self) -> String {
         // TODO: need to add config.base_url_path
         PathBuf::from("/")
@@ -243,19 +548,125 @@ impl Event {
             .to_string()
     }
 
+    pub fn uid(&self) -> &str {
+        &self.uid
+    }
+
+    pub fn recurrence_id(&self) -> Option<DateTime<Utc>> {
+        self.recurrence_id
+    }
+
+    pub fn this_and_future(&self) -> bool {
+        self.this_and_future
+    }
+
+    /// Returns the recurring event this event was expanded from
+    pub fn series(&self) -> Option<&Rc<Event>> {
+        self.series.as_ref()
+    }
+
+    /// Returns the `RRULE` of the series this event belongs to, or of the event itself if it is recurring
+    pub fn series_rrule(&self) -> Option<&str> {
+        match &self.series {
+            Some(series) => series.rrule.as_deref(),
+            None => self.rrule.as_deref(),
+        }
+    }
+
+    /// Returns an English description of the recurrence rule of this event's series
+    ///
+    /// The `UNTIL` date of the rule is shown in the given timezone.
+    pub fn recurrence_description(&self, tz: &ChronoTz) -> Option<String> {
+        let rrule = self.series_rrule()?;
+        match rrule.parse::<RRule<Unvalidated>>() {
+            Ok(rrule) => Some(describe_rrule(&rrule, tz)),
+            Err(e) => {
+                log::warn!("could not describe rrule {:?}: {}", rrule, e);
+                None
+            }
+        }
+    }
+
+    /// Returns the start times of the expanded instances of this recurring event
+    pub fn occurrences(&self) -> &[DateTime<Utc>] {
+        self.occurrences
+            .get()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Records the start times of the expanded instances of this recurring event
+    pub(crate) fn set_occurrences(&self, mut occurrences: Vec<DateTime<Utc>>) -> Result<()> {
+        occurrences.sort();
+        self.occurrences
+            .set(occurrences)
+            .map_err(|_| eyre!("occurrences of event {} were already set", self.uid))
+    }
+
+    /// Returns the start of the next instance in this event's series
+    pub fn next_occurrence(&self) -> Option<DateTime<Utc>> {
+        self.series()?
+            .occurrences()
+            .iter()
+            .find(|o| **o > self.start)
+            .copied()
+    }
+
+    /// Returns the start of the previous instance in this event's series
+    pub fn previous_occurrence(&self) -> Option<DateTime<Utc>> {
+        self.series()?
+            .occurrences()
+            .iter()
+            .rev()
+            .find(|o| **o < self.start)
+            .copied()
+    }
+
     pub fn summary(&self) -> &str {
+        if self.is_redacted() {
+            return BUSY_SUMMARY;
+        }
         self.summary.as_deref().unwrap_or(MISSING_SUMMARY)
     }
 
     pub fn description(&self) -> &str {
+        if self.is_redacted() {
+            return "";
+        }
         self.description.as_deref().unwrap_or(MISSING_DESCRIPTION)
     }
+
+    /// Returns the classification of the event, events without a `CLASS` are public
+    pub fn class(&self) -> EventClass {
+        self.class.unwrap_or(EventClass::Public)
+    }
+
+    fn private_event_policy(&self) -> PrivateEventPolicy {
+        if self.class() == EventClass::Public {
+            PrivateEventPolicy::Show
+        } else {
+            self.calendar_config.private_events.unwrap_or_default()
+        }
+    }
+
+    /// Whether the details of this private event must be replaced with a generic "Busy" block
+    pub fn is_redacted(&self) -> bool {
+        self.private_event_policy() == PrivateEventPolicy::BusyOnly
+    }
+
+    /// Whether this private event must not be shown at all
+    pub fn is_hidden(&self) -> bool {
+        self.private_event_policy() == PrivateEventPolicy::Hide
+    }
     pub fn start(&self) -> DateTime<Utc> {
         self.start
     }
 
+    /// Returns the start of the event in the given timezone
+    ///
+    /// All-day events keep their calendar date and start at local midnight.
     pub fn start_with_timezone(&self, tz: &ChronoTz) -> DateTime<ChronoTz> {
-        self.start.with_timezone(tz)
+        self.floating_with_timezone(self.start, tz)
     }
 
     pub fn end(&self) -> DateTime<Utc> {
@@ -263,7 +674,27 @@ impl Event {
     }
 
     pub fn end_with_timezone(&self, tz: &ChronoTz) -> DateTime<ChronoTz> {
-        (self.start + self.duration).with_timezone(tz)
+        self.floating_with_timezone(self.end(), tz)
+    }
+
+    /// Converts a time of this event into the given timezone, all-day times keep their wall-clock time
+    fn floating_with_timezone(&self, time: DateTime<Utc>, tz: &ChronoTz) -> DateTime<ChronoTz> {
+        if self.all_day {
+            tz.from_local_datetime(&time.naive_utc())
+                .earliest()
+                .unwrap_or_else(|| time.with_timezone(tz))
+        } else {
+            time.with_timezone(tz)
+        }
+    }
+
+    /// The timezone in which the start of this event was given
+    pub fn timezone(&self) -> ChronoTz {
+        self.timezone
+    }
+
+    pub fn all_day(&self) -> bool {
+        self.all_day
     }
 
     pub fn days_with_timezone(&self, tz: &ChronoTz) -> Vec<DateTime<ChronoTz>> {
@@ -276,10 +707,64 @@ impl Event {
         DateRule::daily(start).with_end(end).into_iter().collect()
     }
 
+    pub fn status(&self) -> Option<EventStatus> {
+        self.status
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.status == Some(EventStatus::Cancelled)
+    }
+
+    pub fn categories(&self) -> &[String] {
+        if self.is_redacted() {
+            return &[];
+        }
+        &self.categories
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref().filter(|_| !self.is_redacted())
+    }
+
+    pub fn geo(&self) -> Option<Geo> {
+        self.geo.filter(|_| !self.is_redacted())
+    }
+
+    pub fn organizer(&self) -> Option<&Person> {
+        self.organizer.as_ref().filter(|_| !self.is_redacted())
+    }
+
+    pub fn attendees(&self) -> &[Person] {
+        if self.is_redacted() {
+            return &[];
+        }
+        &self.attendees
+    }
+
+    pub fn contact(&self) -> Option<&str> {
+        self.contact.as_deref().filter(|_| !self.is_redacted())
+    }
+
+    /// Returns the properties of this event which statical does not interpret itself
+    pub fn extra(&self) -> &ExtraProperties {
+        static NO_EXTRA: ExtraProperties = ExtraProperties::new();
+        if self.is_redacted() {
+            return &NO_EXTRA;
+        }
+        &self.extra
+    }
+
     pub fn url(&self) -> &str {
+        if self.is_redacted() {
+            return "";
+        }
         self.url.as_deref().unwrap_or_default()
     }
 
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref().filter(|_| !self.is_redacted())
+    }
+
     pub fn year(&self) -> Year {
         self.start.year()
     }
@@ -320,40 +805,94 @@ impl Event {
         self.start.iso_week().week() as u8
     }
 
+    /// Returns the recurrence set of this event built from its `RRULE`, `RDATE`, and `EXDATE` properties
+    ///
+    /// Events with neither an `RRULE` nor any `RDATE`s are not recurring and return `None`.
     pub fn rrule(&self) -> Result<Option<RRuleSet>> {
-        println!("Attempting to parse rrule: {:?}", self.rrule);
+        log::debug!("attempting to parse rrule: {:?}", self.rrule);
 
-        // ensure that DTSTART is provided in UTC
-        let start_time = self.start().format(RRULE_DTSTART_PARSING_FORMAT);
+        // DTSTART is given in its original timezone so that occurrences keep their wall-clock time across DST changes
+        let timezone = RruleTz::from(self.timezone);
+        let dt_start = self.start().with_timezone(&timezone);
 
-        if let Some(rrule_str) = &self.rrule {
-            let rrule = format!("DTSTART:{}\n{}", start_time, rrule_str).parse()?;
-            Ok(Some(rrule))
-        } else {
-            Ok(None)
-        }
+        let rrule_set = match &self.rrule {
+            Some(rrule_str) => {
+                let rrule = rrule_str.parse::<RRule<Unvalidated>>()?;
+                match rrule.clone().build(dt_start) {
+                    Ok(rrule_set) => rrule_set,
+                    // some producers write UNTIL in local time which is only valid for floating DTSTARTs
+                    Err(e) if timezone != RruleTz::UTC => {
+                        log::warn!(
+                            "could not build rrule {:?} in timezone {}, expanding in UTC instead: {}",
+                            rrule_str,
+                            self.timezone,
+                            e
+                        );
+                        rrule.build(self.start().with_timezone(&RruleTz::UTC))?
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            // RDATEs without an RRULE still make this a recurring event, DTSTART is always the first instance
+            None if !self.rdates.is_empty() => RRuleSet::new(dt_start).rdate(dt_start),
+            None => return Ok(None),
+        };
+
+        let rrule_set = self.rdates.iter().fold(rrule_set, |set, rdate| {
+            set.rdate(rdate.with_timezone(&timezone))
+        });
+        let rrule_set = self.exdates.iter().fold(rrule_set, |set, exdate| {
+            set.exdate(exdate.with_timezone(&timezone))
+        });
+
+        Ok(Some(rrule_set))
     }
 
     pub fn new(
         event: &IcalEvent,
         calendar_config: Rc<CalendarSourceConfig>,
+        timezones: &TimeZoneResolver,
     ) -> Result<(Event, UnparsedProperties)> {
         log::debug!("creating new Event...");
 
         // let calendar_config = Rc::new(calendar_config);
+        let mut uid = None;
+        let mut recurrence_id = None;
+        let mut this_and_future = false;
         let mut summary = None;
         let mut description = None;
         let mut start: Option<DateTime<Utc>> = None;
         let mut end: Option<DateTime<Utc>> = None;
+        let mut duration: Option<Duration> = None;
+        let mut start_is_date = false;
+        let mut start_timezone = chrono_tz::UTC;
         let mut rrule = None;
+        let mut exdates = Vec::new();
+        let mut rdates = Vec::new();
         let mut location = None;
+        let mut geo = None;
+        let mut organizer = None;
+        let mut attendees = Vec::new();
+        let mut contact = None;
         let mut url = None;
+        let mut image = None;
+        let mut status = None;
+        let mut class = None;
+        let mut categories: Vec<String> = Vec::new();
 
         let mut unparsed_properties: UnparsedProperties = HashSet::new();
+        let mut extra = ExtraProperties::new();
 
         for property in &event.properties {
             log::debug!("parsing property: {}: {:?}", property.name, property.value);
             match property.name.as_str() {
+                "UID" => uid = property.value.clone(),
+                "RECURRENCE-ID" => {
+                    recurrence_id = property_to_time(property, timezones)?;
+                    this_and_future = property.params.iter().flatten().any(|(name, values)| {
+                        name == "RANGE" && values.iter().any(|v| v == "THISANDFUTURE")
+                    });
+                }
                 "SUMMARY" => summary = property.value.clone(),
                 // TODO: sanitize html, maybe expand markdown
                 "DESCRIPTION" => {
@@ -365,20 +904,79 @@ impl Event {
                         .map(|v| unescape(&v))
                         .transpose()?
                 }
-                // TODO use the user configured default timezone
-                "DTSTART" => start = property_to_time(property, chrono_tz::UTC)?,
-                // TODO use the user configured default timezone
-                "DTEND" => end = property_to_time(property, chrono_tz::UTC)?,
+                "DTSTART" => {
+                    start = property_to_time(property, timezones)?;
+                    start_is_date = property_is_date(property);
+                    // dates and UTC times are expanded in UTC, everything else in its local timezone
+                    if !start_is_date && !property.value.iter().any(|v| v.ends_with('Z')) {
+                        start_timezone = property_timezone(property, timezones);
+                    }
+                }
+                "DTEND" => end = property_to_time(property, timezones)?,
+                "DURATION" => {
+                    duration = property.value.as_deref().map(parse_duration).transpose()?
+                }
                 "RRULE" => rrule = property.value.clone(),
-                "LOCATION" => location = property.value.clone(),
+                "EXDATE" => exdates.extend(property_to_times(property, timezones)?),
+                "RDATE" => rdates.extend(property_to_times(property, timezones)?),
+                "LOCATION" => location = property.value.as_deref().map(unescape_text),
+                "GEO" => {
+                    geo = property
+                        .value
+                        .as_deref()
+                        .and_then(|v| match Geo::from_str(v) {
+                            Ok(geo) => Some(geo),
+                            Err(e) => {
+                                log::warn!("{:#}", e);
+                                None
+                            }
+                        })
+                }
+                "ORGANIZER" => organizer = Some(Person::from_property(property)),
+                "ATTENDEE" => attendees.push(Person::from_property(property)),
+                "CONTACT" => contact = property.value.as_deref().map(unescape_text),
                 "URL" => url = property.value.clone(),
+                // images embedded as binary data are kept as extra properties
+                "IMAGE" if image.is_none() && !property_is_binary(property) => {
+                    image = property.value.clone()
+                }
+                "CLASS" => class = property.value.as_deref().map(EventClass::from),
+                "CATEGORIES" => {
+                    // names which share a slug would otherwise list the event twice on the same category page
+                    for category in property.value.iter().flat_map(|v| split_text_list(v)) {
+                        let slug = category_view::category_slug(&category);
+                        if !categories
+                            .iter()
+                            .any(|c: &String| category_view::category_slug(c) == slug)
+                        {
+                            categories.push(category);
+                        }
+                    }
+                }
+                "STATUS" => {
+                    // an unknown status is not worth losing the event over
+                    status =
+                        property
+                            .value
+                            .as_deref()
+                            .and_then(|v| match EventStatus::from_str(v) {
+                                Ok(status) => Some(status),
+                                Err(e) => {
+                                    log::warn!("{}", e);
+                                    None
+                                }
+                            })
+                }
                 _ => {
                     log::trace!("adding unparsed property: {}", property.name);
                     unparsed_properties.insert(property.name.clone());
-                    // TODO collect unparsed params as well
-                    // if let Some(params) = property.params {
-                    //     println!("{:#?}", params);
-                    // }
+                    extra
+                        .entry(property.name.clone())
+                        .or_default()
+                        .push(ExtraProperty {
+                            value: property.value.clone().unwrap_or_default(),
+                            params: property.params.iter().flatten().cloned().collect(),
+                        });
                 }
             }
         }
@@ -390,108 +988,299 @@ impl Event {
         if start.is_none() {
             bail!("event has no start time")
         }
-        if end.is_none() {
-            bail!("event has no end time")
-        }
+        let start = start.unwrap();
+
+        // DTEND takes precedence over DURATION, without either RFC 5545 defines a default length
+        let duration = match (end, duration) {
+            (Some(end), _) => end - start,
+            (None, Some(duration)) => duration,
+            (None, None) if start_is_date => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+
+        // events without a UID get one generated from their contents so they still have a stable identity
+        let uid = uid.unwrap_or_else(|| generated_uid(&calendar_config.name, event));
 
         // TODO parse the rrule here, store None if it does not parse
         Ok((
             Event {
                 calendar_config,
+                uid,
+                recurrence_id,
+                this_and_future,
+                all_day: start_is_date,
                 summary,
                 description,
-                start: start.unwrap(),
-                duration: end.unwrap() - start.unwrap(),
+                start,
+                timezone: start_timezone,
+                duration,
                 rrule,
+                exdates,
+                rdates,
                 location,
+                geo,
+                organizer,
+                attendees,
+                contact,
                 url,
-                event_number: EVENT_COUNT.fetch_add(1, Relaxed),
+                image,
+                status,
+                class,
+                categories,
+                extra,
+                series: None,
+                occurrences: OnceCell::new(),
             },
             unparsed_properties,
         ))
     }
 
+    /// Distinguishes an event without a UID from identical events before it in the same calendar
+    ///
+    /// `position` counts the identical events, so the first one keeps its generated UID.
+    pub(crate) fn disambiguate_generated_uid(&mut self, position: usize) {
+        if position > 1 {
+            self.uid = match self.uid.split_once('@') {
+                Some((hash, domain)) => format!("{}-{}@{}", hash, position, domain),
+                None => format!("{}-{}", self.uid, position),
+            };
+        }
+    }
+
     /// Creates a duplicate event with a different start datetime.
     ///
     /// This is useful when we are creating events from rrule expansions.
-    pub fn duplicate_with_date(&self, date: DateTime<ChronoTz>) -> Event {
-        // TODO might want to link this event back to its parent event in some way, maybe even have a separate event class
+    /// The `recurrence_id` is the original start time of the series instance that the duplicate represents,
+    /// and `series` is the recurring event it belongs to.
+    pub fn duplicate_with_date(
+        &self,
+        date: DateTime<ChronoTz>,
+        recurrence_id: DateTime<Utc>,
+        series: &Rc<Event>,
+    ) -> Event {
         Event {
             calendar_config: self.calendar_config.clone(),
+            uid: self.uid.clone(),
+            recurrence_id: Some(recurrence_id),
+            this_and_future: false,
+            all_day: self.all_day,
             summary: self.summary.clone(),
             description: self.description.clone(),
             start: date.with_timezone(&Utc),
+            timezone: self.timezone,
             duration: self.duration,
             // we're un-setting the rrule to prevent recursion issues here
             rrule: None,
+            exdates: Vec::new(),
+            rdates: Vec::new(),
             location: self.location.clone(),
+            geo: self.geo,
+            organizer: self.organizer.clone(),
+            attendees: self.attendees.clone(),
+            contact: self.contact.clone(),
             url: self.url.clone(),
-            event_number: EVENT_COUNT.fetch_add(1, Relaxed),
+            image: self.image.clone(),
+            status: self.status,
+            class: self.class,
+            categories: self.categories.clone(),
+            extra: self.extra.clone(),
+            series: Some(series.clone()),
+            occurrences: OnceCell::new(),
         }
     }
 }
 
+/// Generates a UID for an event without one from its calendar name and all of its properties
+///
+/// Events only share a generated UID if they are identical, see [`Event::disambiguate_generated_uid`].
+fn generated_uid(calendar_name: &str, event: &IcalEvent) -> String {
+    let lines: Vec<String> = event
+        .properties
+        .iter()
+        .map(|property| {
+            format!(
+                "{}{:?}:{}",
+                property.name,
+                property.params,
+                property.value.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    let parts: Vec<&str> = iter::once(calendar_name)
+        .chain(lines.iter().map(String::as_str))
+        .collect();
+    let hash = stable_hash(&parts);
+    log::debug!("event has no UID, generated: {:016x}", hash);
+    format!("{:016x}@statical", hash)
+}
+
 /// Given a time based ical property, parse it into a OffsetDateTime
 fn property_to_time(
     property: &ical::property::Property,
-    default_timezone: ChronoTz,
+    timezones: &TimeZoneResolver,
 ) -> Result<Option<DateTime<Utc>>> {
-    // this map holds the patterns to match, the corresponding format strings for parsing, and the type of parsing method
-    // TODO use lazy_static! here
-    let regex_fmt_map = vec![
-        (r"^(\d+T\d+)Z$", "%Y%m%dT%H%M%SZ", ParseType::ParseDateTime),
-        (r"^(\d+T\d+)$", "%Y%m%dT%H%M%S", ParseType::ParseDateTime),
-        (r"^(\d+)$", "%Y%m%d", ParseType::ParseDate),
-    ];
-    let set = RegexSet::new(regex_fmt_map.iter().map(|r| r.0))?;
+    Ok(property_to_times(property, timezones)?.into_iter().next())
+}
 
+/// Given a time based ical property which may hold a comma separated list of values, parse all of them
+///
+/// This is needed for properties like `EXDATE` and `RDATE` which can list several dates at once.
+/// `RDATE` values of type `PERIOD` only contribute their start time.
+fn property_to_times(
+    property: &ical::property::Property,
+    timezones: &TimeZoneResolver,
+) -> Result<Vec<DateTime<Utc>>> {
     let prop_value = &property
         .value
         .as_ref()
         .ok_or(eyre!("no value for this property"))?;
     log::debug!("prop_value: {}", prop_value);
 
-    let matches: Vec<_> = set.matches(prop_value).into_iter().collect();
-    log::debug!("matches: {:?}", matches);
+    let timezone = property_timezone(property, timezones);
 
-    // TODO clean up timezone logic, looks like there are inefficiencies and bugs
-    // let timezone: chrono_tz::Tz = UTC;
-    let timezone: chrono_tz::Tz = if let Some(params) = &property.params {
-        log::debug!("property has parameters, searching for TZID...");
-        // if necessary, parse the primitive time and zone separately
-        match params.iter().find(|(name, _zones)| name == "TZID") {
-            Some((_, zones)) => {
-                log::debug!("found TZID, zones: {:?}", zones);
-                match zones
-                    .first()
-                    // TODO replace expect calls with proper error handling
-                    .map(|tz_name| {
-                        tz_name
-                            .parse::<ChronoTz>()
-                            .expect("could not parse timezone")
-                    }) {
-                    Some(tz) => {
-                        log::debug!("returning timezone: {}", tz);
-                        tz
-                    }
-                    None => {
-                        log::debug!("returning default timezone");
-                        default_timezone
-                    }
-                }
-            }
-            None => {
-                log::debug!("returning default timezone");
-                default_timezone
-            }
+    prop_value
+        .split(',')
+        // a PERIOD is written as start/end or start/duration, we only need the start
+        .map(|value| value.split('/').next().unwrap_or(value).trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value_to_time(value, timezone))
+        .collect()
+}
+
+/// Returns the timezone given by the `TZID` parameter of a property, or the default timezone if it has none
+fn property_timezone(
+    property: &ical::property::Property,
+    timezones: &TimeZoneResolver,
+) -> ChronoTz {
+    match property
+        .params
+        .iter()
+        .flatten()
+        .find(|(name, _zones)| name == "TZID")
+        .and_then(|(_, zones)| zones.first())
+    {
+        Some(tzid) => {
+            log::debug!("found TZID: {}", tzid);
+            timezones.resolve(tzid)
+        }
+        None => {
+            log::debug!("returning default timezone");
+            timezones.default_timezone()
         }
-    } else {
-        // set a default timezone
-        log::debug!("returning default timezone");
-        default_timezone
+    }
+}
+
+/// Unescape an ical text value, e.g. `Room 2\, Floor 1` becomes `Room 2, Floor 1`
+fn unescape_text(value: &str) -> String {
+    split_text_list_with(value, None).concat()
+}
+
+/// Split a comma separated list of text values, as used by `CATEGORIES`, honoring escaped commas
+fn split_text_list(value: &str) -> Vec<String> {
+    split_text_list_with(value, Some(','))
+        .into_iter()
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Unescape an ical text value and split it on unescaped occurrences of `separator`
+fn split_text_list_with(value: &str, separator: Option<char>) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => values.last_mut().unwrap().push('\n'),
+                Some(escaped) => values.last_mut().unwrap().push(escaped),
+                None => {}
+            },
+            c if Some(c) == separator => values.push(String::new()),
+            c => values.last_mut().unwrap().push(c),
+        }
+    }
+    values
+}
+
+/// Whether a time based ical property holds a date rather than a date-time
+fn property_is_date(property: &ical::property::Property) -> bool {
+    property
+        .params
+        .iter()
+        .flatten()
+        .any(|(name, values)| name == "VALUE" && values.iter().any(|v| v == "DATE"))
+        || property
+            .value
+            .as_deref()
+            .is_some_and(|v| v.len() == 8 && v.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether a property holds inline binary data rather than text or a URI
+fn property_is_binary(property: &ical::property::Property) -> bool {
+    property.params.iter().flatten().any(|(name, values)| {
+        (name == "VALUE" && values.iter().any(|v| v.eq_ignore_ascii_case("BINARY")))
+            || name == "ENCODING"
+    })
+}
+
+/// Parse an ical duration value such as `PT1H30M`, `P1D` or `-P2W`
+fn parse_duration(value: &str) -> Result<Duration> {
+    let (negative, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.trim().trim_start_matches('+')),
     };
+    let rest = rest
+        .strip_prefix('P')
+        .ok_or(eyre!("duration does not start with P: {}", value))?;
 
-    let first_match = matches.first().expect("no matches found");
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            unit => {
+                let amount: i64 = number
+                    .parse()
+                    .wrap_err_with(|| format!("could not parse duration: {}", value))?;
+                number.clear();
+                duration = duration
+                    + match (unit, in_time) {
+                        ('W', false) => Duration::weeks(amount),
+                        ('D', false) => Duration::days(amount),
+                        ('H', true) => Duration::hours(amount),
+                        ('M', true) => Duration::minutes(amount),
+                        ('S', true) => Duration::seconds(amount),
+                        _ => bail!("unknown unit {:?} in duration: {}", unit, value),
+                    };
+            }
+        }
+    }
+    if !number.is_empty() {
+        bail!("duration is missing a unit: {}", value)
+    }
+
+    Ok(if negative { -duration } else { duration })
+}
+
+/// Parse a single ical date or date-time value in the given timezone
+fn value_to_time(prop_value: &str, timezone: ChronoTz) -> Result<DateTime<Utc>> {
+    // this map holds the patterns to match, the corresponding format strings for parsing, and the type of parsing method
+    // TODO use lazy_static! here
+    let regex_fmt_map = [
+        (r"^(\d+T\d+)Z$", "%Y%m%dT%H%M%SZ", ParseType::ParseDateTime),
+        (r"^(\d+T\d+)$", "%Y%m%dT%H%M%S", ParseType::ParseDateTime),
+        (r"^(\d+)$", "%Y%m%d", ParseType::ParseDate),
+    ];
+    let set = RegexSet::new(regex_fmt_map.iter().map(|r| r.0))?;
+
+    let matches: Vec<_> = set.matches(prop_value).into_iter().collect();
+    log::debug!("matches: {:?}", matches);
+
+    let first_match = matches
+        .first()
+        .ok_or(eyre!("could not recognize time format of: {}", prop_value))?;
 
     // parse the time without zone information
     let fmt = regex_fmt_map[*first_match].1;
@@ -499,6 +1288,12 @@ fn property_to_time(
 
     let primitive_time: DateTime<Utc> = match regex_fmt_map[*first_match].2 {
         ParseType::ParseDateTime => {
+            // values ending in Z are always UTC regardless of any TZID
+            let timezone = if prop_value.ends_with('Z') {
+                chrono_tz::UTC
+            } else {
+                timezone
+            };
             match NaiveDateTime::parse_from_str(prop_value, fmt)
                 .wrap_err("could not parse this time")?
                 .and_local_timezone(timezone)
@@ -522,5 +1317,214 @@ fn property_to_time(
     };
 
     // adjust the timezone
-    Ok(Some(primitive_time))
+    Ok(primitive_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{America::Los_Angeles, Asia::Tokyo};
+    use ical::IcalParser;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Parses the properties of a single event with the given extra source settings
+    fn parse_event(properties: &str, source_settings: &str) -> Event {
+        let ics = format!(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\n{}END:VEVENT\nEND:VCALENDAR\n",
+            properties
+        );
+        let calendar = IcalParser::new(ics.as_bytes())
+            .next()
+            .expect("no calendar found")
+            .expect("could not parse calendar");
+        let source_config = Rc::new(
+            toml_edit::de::from_str(&format!(
+                "source = 'test.ics'\nname = 'test'\ncolor = 'red'\n{}",
+                source_settings
+            ))
+            .expect("could not parse source config"),
+        );
+        let timezones = TimeZoneResolver::new(&[], chrono_tz::UTC, chrono_tz::UTC);
+        Event::new(&calendar.events[0], source_config, &timezones)
+            .expect("could not parse event")
+            .0
+    }
+
+    #[test]
+    fn all_day_events_keep_their_dates_in_every_timezone() {
+        let event = parse_event(
+            indoc! {"
+                SUMMARY:Spring Fair
+                DTSTART;VALUE=DATE:20240320
+                DTEND;VALUE=DATE:20240322
+            "},
+            "",
+        );
+
+        assert!(event.all_day());
+        for tz in [Tokyo, Los_Angeles] {
+            let days: Vec<String> = event
+                .days_with_timezone(&tz)
+                .iter()
+                .map(|day| day.format("%Y-%m-%d %H:%M").to_string())
+                .collect();
+            assert_eq!(
+                days,
+                vec!["2024-03-20 00:00", "2024-03-21 00:00"],
+                "in {}",
+                tz
+            );
+        }
+    }
+
+    #[test]
+    fn hides_personal_data_of_organizer_attendees_and_contact() {
+        let event = parse_event(
+            indoc! {"
+                SUMMARY:Board Meeting
+                DTSTART:20240320T180000Z
+                ORGANIZER;CN=Jane Doe:mailto:jane@example.org
+                ATTENDEE;CN=John Roe:mailto:john@example.org
+                CONTACT:Jane Doe\\, +1-555-0100\\, jane@example.org
+            "},
+            "",
+        );
+        let config = Config {
+            hide_personal_data: true,
+            ..Default::default()
+        };
+        let context = event.context(&config);
+
+        assert_eq!(
+            context.organizer,
+            Some(Person {
+                name: Some("Jane Doe".into()),
+                email: None
+            })
+        );
+        assert!(context.attendees.is_empty());
+        assert_eq!(context.contact, None);
+        assert_eq!(
+            event.context(&Config::default()).contact.as_deref(),
+            Some("Jane Doe, +1-555-0100, jane@example.org")
+        );
+    }
+
+    #[test]
+    fn redacted_events_leave_out_identifying_details() {
+        let properties = indoc! {"
+            UID:therapy@example.org
+            SUMMARY:Therapy Appointment
+            DESCRIPTION:Bring the referral letter
+            LOCATION:Suite 200
+            CLASS:PRIVATE
+            DTSTART:20240320T180000Z
+            RRULE:FREQ=WEEKLY;COUNT=4
+            X-CUSTOM:secret
+        "};
+        let series = Rc::new(parse_event(properties, "private_events = 'busy-only'"));
+        let instance = series.duplicate_with_date(
+            series.start().with_timezone(&chrono_tz::UTC),
+            series.start(),
+            &series,
+        );
+        let context = instance.context(&Config::default());
+
+        assert!(context.redacted);
+        assert_eq!(context.summary, BUSY_SUMMARY);
+        assert_eq!(context.description, "");
+        assert_eq!(context.uid, None);
+        assert_eq!(context.recurrence_id, None);
+        assert_eq!(context.rrule, None);
+        assert_eq!(context.recurrence, None);
+        assert_eq!(context.location, None);
+        assert!(context.extra.is_empty());
+
+        let shown = parse_event(properties, "").context(&Config::default());
+        assert_eq!(shown.uid.as_deref(), Some("therapy@example.org"));
+    }
+
+    #[test]
+    fn passes_extension_properties_to_templates() {
+        let event = parse_event(
+            indoc! {"
+                SUMMARY:Launch Party
+                DTSTART:20240320T180000Z
+                X-ALT-DESC;FMTTYPE=text/html:<b>Launch</b>
+                X-MICROSOFT-CDO-BUSYSTATUS:FREE
+            "},
+            "",
+        );
+        let context = event.context(&Config::default());
+
+        assert_eq!(
+            context.extra["X-ALT-DESC"],
+            vec![ExtraProperty {
+                value: "<b>Launch</b>".into(),
+                params: BTreeMap::from([("FMTTYPE".into(), vec!["text/html".into()])]),
+            }]
+        );
+        assert_eq!(context.extra["X-MICROSOFT-CDO-BUSYSTATUS"][0].value, "FREE");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("P1D").unwrap(), Duration::days(1));
+        assert_eq!(parse_duration("P2W").unwrap(), Duration::weeks(2));
+        assert_eq!(
+            parse_duration("P1DT12H").unwrap(),
+            Duration::hours(36),
+            "days and times should add up"
+        );
+        assert_eq!(parse_duration("-PT15M").unwrap(), Duration::minutes(-15));
+        assert!(parse_duration("1H").is_err());
+        assert!(parse_duration("PT1").is_err());
+    }
+
+    #[test]
+    fn parses_text_values() {
+        assert_eq!(
+            split_text_list(r"Workshops,Talks\, Lectures, ,Rust"),
+            vec!["Workshops", "Talks, Lectures", "Rust"]
+        );
+        assert_eq!(
+            unescape_text(r"Library\, Room 2\nSecond floor"),
+            "Library, Room 2\nSecond floor"
+        );
+    }
+
+    #[test]
+    fn keeps_categories_sharing_a_slug_once() {
+        let event = parse_event(
+            indoc! {"
+                UID:open-mic
+                SUMMARY:Open Mic
+                DTSTART:20240320T190000Z
+                CATEGORIES:Music,music,Art
+                CATEGORIES:MUSIC,C++,C
+            "},
+            "",
+        );
+
+        assert_eq!(event.categories(), ["Music", "Art", "C++", "C"]);
+    }
+
+    #[test]
+    fn parses_geo() {
+        assert_eq!(
+            "33.4484;-112.0740".parse::<Geo>().unwrap(),
+            Geo {
+                latitude: 33.4484,
+                longitude: -112.074
+            }
+        );
+        assert!("33.4484".parse::<Geo>().is_err());
+        assert!("NaN;-112.0740".parse::<Geo>().is_err());
+        assert!("33.4484;inf".parse::<Geo>().is_err());
+        assert!("91.0;0.0".parse::<Geo>().is_err());
+        assert!("0.0;-180.5".parse::<Geo>().is_err());
+    }
 }
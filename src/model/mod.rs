@@ -5,4 +5,8 @@ pub mod calendar_source;
 pub mod day;
 pub mod event;
 pub mod month;
+pub mod recurrence;
+pub mod sources;
+pub mod timezone;
 pub mod week;
+pub mod windows_timezones;
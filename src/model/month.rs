@@ -88,10 +88,8 @@ impl Month<'_> {
             .events_by_day
             // TODO: I doubt that we need to adjust the timezone here, probably remove it
             .range((Included(start_day), Included(end_day)))
-            .into_iter()
             .next()
-            .map(|(_first_date, events)| events.first())
-            .flatten();
+            .and_then(|(_first_date, events)| events.first());
 
         Ok(first_event)
     }
@@ -0,0 +1,260 @@
+use chrono::Weekday;
+use chrono_tz::Tz as ChronoTz;
+use itertools::Itertools;
+use rrule::{Frequency, NWeekday, RRule, Unvalidated};
+
+const UNTIL_FORMAT: &str = "%B %-d, %Y";
+
+const WORKDAYS: [Weekday; 5] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+];
+
+/// Describe a recurrence rule in English, e.g. "Every 2nd Tuesday of the month until June 3, 2025"
+///
+/// The `UNTIL` date is given in the provided timezone.
+/// Parts of a rule which have no simple English equivalent, like `BYYEARDAY`, are left out of the description.
+pub fn describe_rrule(rrule: &RRule<Unvalidated>, tz: &ChronoTz) -> String {
+    let freq = rrule.get_freq();
+    let interval = rrule.get_interval().max(1);
+    let period = if interval == 1 {
+        format!("every {}", unit_name(freq))
+    } else {
+        format!("every {} {}s", interval, unit_name(freq))
+    };
+
+    // a single BYSETPOS picks the nth of the listed weekdays, e.g. BYDAY=TU;BYSETPOS=2
+    let weekdays: Vec<NWeekday> = match (rrule.get_by_weekday(), rrule.get_by_set_pos()) {
+        ([NWeekday::Every(day)], [pos]) => vec![NWeekday::Nth(*pos as i16, *day)],
+        (weekdays, _) => weekdays.to_vec(),
+    };
+    let every_days: Vec<Weekday> = weekdays
+        .iter()
+        .filter_map(|d| match d {
+            NWeekday::Every(day) => Some(*day),
+            NWeekday::Nth(..) => None,
+        })
+        .collect();
+    let nth_days: Vec<String> = weekdays
+        .iter()
+        .filter_map(|d| match d {
+            NWeekday::Nth(n, day) => Some(format!("{} {}", ordinal(*n as i32), weekday_name(*day))),
+            NWeekday::Every(_) => None,
+        })
+        .collect();
+    // days counted from the end of the month are listed last
+    let month_days: Vec<String> = rrule
+        .get_by_month_day()
+        .iter()
+        .sorted_by_key(|d| (**d < 0, d.abs()))
+        .map(|d| match d {
+            -1 => "last day".to_owned(),
+            d => ordinal(*d as i32),
+        })
+        .collect();
+    let months: Vec<&str> = rrule
+        .get_by_month()
+        .iter()
+        .filter_map(|m| month_name(*m))
+        .collect();
+
+    let mut description = match freq {
+        Frequency::Daily | Frequency::Weekly if !every_days.is_empty() => {
+            if interval == 1 {
+                format!("every {}", describe_weekdays(&every_days))
+            } else {
+                format!("{} on {}", period, describe_weekdays(&every_days))
+            }
+        }
+        Frequency::Monthly if !nth_days.is_empty() => {
+            if interval == 1 {
+                format!("every {} of the month", join_english(&nth_days))
+            } else {
+                format!("{} on the {}", period, join_english(&nth_days))
+            }
+        }
+        Frequency::Monthly if !month_days.is_empty() => {
+            format!("{} on the {}", period, join_english(&month_days))
+        }
+        Frequency::Monthly if !every_days.is_empty() => {
+            format!("{} on every {}", period, describe_weekdays(&every_days))
+        }
+        Frequency::Yearly if !months.is_empty() && !nth_days.is_empty() => format!(
+            "{} on the {} of {}",
+            period,
+            join_english(&nth_days),
+            join_english(&months)
+        ),
+        Frequency::Yearly if !months.is_empty() && !month_days.is_empty() => format!(
+            "{} on {} {}",
+            period,
+            join_english(&months),
+            join_english(&month_days)
+        ),
+        Frequency::Yearly if !months.is_empty() => {
+            format!("{} in {}", period, join_english(&months))
+        }
+        _ => period,
+    };
+
+    match (rrule.get_count(), rrule.get_until()) {
+        (Some(1), _) => description.push_str(", once"),
+        (Some(count), _) => description.push_str(&format!(", {} times", count)),
+        (None, Some(until)) => description.push_str(&format!(
+            " until {}",
+            until.with_timezone(tz).format(UNTIL_FORMAT)
+        )),
+        (None, None) => {}
+    }
+
+    capitalize(&description)
+}
+
+fn unit_name(freq: Frequency) -> &'static str {
+    match freq {
+        Frequency::Yearly => "year",
+        Frequency::Monthly => "month",
+        Frequency::Weekly => "week",
+        Frequency::Daily => "day",
+        Frequency::Hourly => "hour",
+        Frequency::Minutely => "minute",
+        Frequency::Secondly => "second",
+    }
+}
+
+fn describe_weekdays(days: &[Weekday]) -> String {
+    let mut days: Vec<Weekday> = days.iter().copied().unique().collect();
+    days.sort_by_key(|d| d.num_days_from_monday());
+    if days == WORKDAYS {
+        return "weekday".to_owned();
+    }
+    join_english(&days.into_iter().map(weekday_name).collect::<Vec<_>>())
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+fn month_name(month: u8) -> Option<&'static str> {
+    [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ]
+    .get(usize::from(month).checked_sub(1)?)
+    .copied()
+}
+
+/// Turn a position into an English ordinal, negative positions count from the end
+fn ordinal(n: i32) -> String {
+    match n {
+        -1 => "last".to_owned(),
+        n if n < 0 => format!("{} to last", ordinal(-n)),
+        n => {
+            let suffix = match (n % 10, n % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", n, suffix)
+        }
+    }
+}
+
+/// Join a list like "a, b and c"
+fn join_english<S: AsRef<str>>(items: &[S]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.as_ref().to_owned(),
+        [rest @ .., last] => format!(
+            "{} and {}",
+            rest.iter().map(AsRef::as_ref).join(", "),
+            last.as_ref()
+        ),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn describe(rrule: &str) -> String {
+        describe_rrule(
+            &rrule.parse().expect("could not parse rrule"),
+            &chrono_tz::America::New_York,
+        )
+    }
+
+    #[test]
+    fn describes_rules() {
+        assert_eq!(describe("FREQ=DAILY"), "Every day");
+        assert_eq!(
+            describe("FREQ=DAILY;INTERVAL=3;COUNT=5"),
+            "Every 3 days, 5 times"
+        );
+        assert_eq!(
+            describe("FREQ=WEEKLY;BYDAY=MO,WE"),
+            "Every Monday and Wednesday"
+        );
+        assert_eq!(
+            describe("FREQ=WEEKLY;BYDAY=FR,TU,MO,TH,WE"),
+            "Every weekday"
+        );
+        assert_eq!(
+            describe("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH,SA"),
+            "Every 2 weeks on Tuesday, Thursday and Saturday"
+        );
+        assert_eq!(
+            describe("FREQ=MONTHLY;BYDAY=2TU;UNTIL=20250603T040000Z"),
+            "Every 2nd Tuesday of the month until June 3, 2025"
+        );
+        assert_eq!(
+            describe("FREQ=MONTHLY;BYDAY=FR;BYSETPOS=-1"),
+            "Every last Friday of the month"
+        );
+        assert_eq!(
+            describe("FREQ=MONTHLY;INTERVAL=3;BYMONTHDAY=1,-1"),
+            "Every 3 months on the 1st and last day"
+        );
+        assert_eq!(
+            describe("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH"),
+            "Every year on the 4th Thursday of November"
+        );
+        assert_eq!(
+            describe("FREQ=YEARLY;BYMONTH=7;BYMONTHDAY=4"),
+            "Every year on July 4th"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use humantime::parse_duration;
+use ical::parser::ical::component::{IcalCalendar, IcalTimeZone};
+use ical::IcalParser;
+use roxmltree::{Document, Node};
+
+use super::FailedItem;
+use crate::configuration::caldav_query::CalDavQuery;
+
+const DAV_NAMESPACE: &str = "DAV:";
+const CALDAV_NAMESPACE: &str = "urn:ietf:params:xml:ns:caldav";
+
+/// The time format of the CalDAV `time-range` element
+const TIME_RANGE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The start and end of the events requested around the given today date
+pub(crate) fn time_range(
+    today: DateTime<Utc>,
+    query: &CalDavQuery,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let duration = |text: &str| {
+        Duration::from_std(
+            parse_duration(text)
+                .wrap_err_with(|| format!("could not parse caldav duration: {:?}", text))?,
+        )
+        .wrap_err("could not convert standard duration into Chrono::Duration")
+    };
+    Ok((
+        today - duration(&query.past)?,
+        today + duration(&query.future)?,
+    ))
+}
+
+/// The body of a calendar-query REPORT for the events overlapping the given time range
+///
+/// See section 7.8 of RFC 4791 for the format of this request.
+pub(crate) fn calendar_query(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="{}" xmlns:C="{}">
+  <D:prop>
+    <D:getetag/>
+    <C:calendar-data/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{}" end="{}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>
+"#,
+        DAV_NAMESPACE,
+        CALDAV_NAMESPACE,
+        start.format(TIME_RANGE_FORMAT),
+        end.format(TIME_RANGE_FORMAT)
+    )
+}
+
+/// Read the multistatus response of a calendar-query into a single calendar
+///
+/// Every resource of a collection holds its own iCalendar object, so their events and timezones are gathered
+/// together. Resources which cannot be parsed are returned separately, identified by their href.
+pub(crate) fn read_calendar(contents: &str, name: &str) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let document = Document::parse(contents).wrap_err("could not parse CalDAV response")?;
+    let root = document.root_element();
+    if !root.has_tag_name((DAV_NAMESPACE, "multistatus")) {
+        bail!(
+            "CalDAV response is not a multistatus: {:?}",
+            root.tag_name().name()
+        );
+    }
+
+    let mut calendar = IcalCalendar::new();
+    calendar
+        .properties
+        .push(super::property("X-WR-CALNAME", name));
+    let mut failures = Vec::new();
+
+    for response in root
+        .children()
+        .filter(|node| node.has_tag_name((DAV_NAMESPACE, "response")))
+    {
+        let href = child(response, DAV_NAMESPACE, "href")
+            .and_then(|href| href.text())
+            .unwrap_or_default()
+            .trim();
+        let Some(data) = calendar_data(response) else {
+            log::debug!("skipping CalDAV resource without calendar data: {}", href);
+            continue;
+        };
+        match read_resource(data) {
+            Ok(resources) => {
+                for resource in resources {
+                    calendar.events.extend(resource.events);
+                    for timezone in resource.timezones {
+                        if !calendar
+                            .timezones
+                            .iter()
+                            .any(|known| tzid(known) == tzid(&timezone))
+                        {
+                            calendar.timezones.push(timezone);
+                        }
+                    }
+                }
+            }
+            Err(error) => failures.push(FailedItem {
+                context: format!("resource {}", href),
+                error,
+            }),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+/// The calendar data of the successful properties of a response
+fn calendar_data<'a>(response: Node<'a, 'a>) -> Option<&'a str> {
+    response
+        .children()
+        .filter(|node| node.has_tag_name((DAV_NAMESPACE, "propstat")))
+        .filter(|propstat| {
+            child(*propstat, DAV_NAMESPACE, "status")
+                .and_then(|status| status.text())
+                .is_none_or(|status| status.contains(" 200 "))
+        })
+        .filter_map(|propstat| child(propstat, DAV_NAMESPACE, "prop"))
+        .find_map(|prop| child(prop, CALDAV_NAMESPACE, "calendar-data"))
+        .and_then(|data| data.text())
+}
+
+fn child<'a>(node: Node<'a, 'a>, namespace: &str, name: &str) -> Option<Node<'a, 'a>> {
+    node.children()
+        .find(|child| child.has_tag_name((namespace, name)))
+}
+
+fn read_resource(data: &str) -> Result<Vec<IcalCalendar>> {
+    let calendars = IcalParser::new(data.trim().as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("could not parse calendar data")?;
+    if calendars.is_empty() {
+        bail!("resource has no calendar data");
+    }
+    Ok(calendars)
+}
+
+fn tzid(timezone: &IcalTimeZone) -> Option<&str> {
+    timezone
+        .properties
+        .iter()
+        .find(|property| property.name == "TZID")
+        .and_then(|property| property.value.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn gathers_resources_and_reports_failures() {
+        let (calendar, failures) = read_calendar(
+            include_str!("../../../tests/fixtures/caldav-report.xml"),
+            "shared",
+        )
+        .expect("could not read caldav response");
+
+        assert_eq!(calendar.events.len(), 2);
+        assert_eq!(calendar.timezones.len(), 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].context,
+            "resource /remote.php/dav/calendars/club/shared/broken.ics"
+        );
+    }
+
+    #[test]
+    fn queries_the_configured_time_range() {
+        let today = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let query = CalDavQuery {
+            past: "30 days".into(),
+            future: "60 days".into(),
+        };
+        let (start, end) = time_range(today, &query).expect("could not get time range");
+
+        assert!(calendar_query(start, end)
+            .contains(r#"<C:time-range start="20240214T000000Z" end="20240514T000000Z"/>"#));
+    }
+}
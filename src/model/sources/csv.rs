@@ -0,0 +1,285 @@
+use ::csv::{Position, ReaderBuilder, StringRecord, Trim};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+
+use super::{derived_uid, FailedItem, SourceDate, SourceEvent};
+use crate::configuration::csv_mapping::CsvMapping;
+
+/// Formats tried for the time columns when the source does not set a `time_format`
+const TIME_FORMATS: &[&str] = &["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p", "%I:%M%p"];
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// The positions of the mapped columns in the header row
+struct Columns {
+    title: usize,
+    date: usize,
+    start_time: Option<usize>,
+    end_time: Option<usize>,
+    location: Option<usize>,
+    url: Option<usize>,
+    description: Option<usize>,
+    uid: Option<usize>,
+}
+
+impl Columns {
+    fn new(headers: &StringRecord, mapping: &CsvMapping) -> Result<Columns> {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(name.trim()))
+        };
+        Ok(Columns {
+            title: find(&mapping.title)
+                .ok_or_else(|| eyre!("CSV has no {:?} column for titles", mapping.title))?,
+            date: find(&mapping.date)
+                .ok_or_else(|| eyre!("CSV has no {:?} column for dates", mapping.date))?,
+            start_time: find(&mapping.start_time),
+            end_time: find(&mapping.end_time),
+            location: find(&mapping.location),
+            url: find(&mapping.url),
+            description: find(&mapping.description),
+            uid: find(&mapping.uid),
+        })
+    }
+}
+
+/// Read CSV data into a calendar with one event per row
+///
+/// Rows which cannot be read are returned separately, identified by the line on which they start, i.e. the
+/// header is row 1 and the rows are numbered as in a spreadsheet unless a cell spans several lines.
+pub(crate) fn read_calendar(
+    contents: &str,
+    name: &str,
+    mapping: &CsvMapping,
+) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let delimiter = u8::try_from(mapping.delimiter).map_err(|_| {
+        eyre!(
+            "CSV delimiter must be a single byte character: {:?}",
+            mapping.delimiter
+        )
+    })?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(contents.as_bytes());
+    let columns = Columns::new(
+        reader.headers().wrap_err("could not read CSV header row")?,
+        mapping,
+    )?;
+
+    let mut calendar = IcalCalendar::new();
+    calendar
+        .properties
+        .push(super::property("X-WR-CALNAME", name));
+    let mut failures = Vec::new();
+
+    for record in reader.records() {
+        let (line, event) = match record {
+            Ok(record) => (
+                record.position().map(|p| record_line(contents, p)),
+                // spreadsheets often export trailing empty rows
+                if record.iter().all(str::is_empty) {
+                    Ok(None)
+                } else {
+                    read_row(&record, &columns, mapping, name).map(Some)
+                },
+            ),
+            Err(error) => (
+                error.position().map(|p| record_line(contents, p)),
+                Err(error).wrap_err("could not read CSV row"),
+            ),
+        };
+        match event {
+            Ok(Some(event)) => calendar.events.push(event),
+            Ok(None) => {}
+            Err(error) => failures.push(FailedItem {
+                context: line
+                    .map_or_else(|| "unknown row".to_owned(), |line| format!("row {}", line)),
+                error,
+            }),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+/// The line on which a record starts, with the header on line 1
+///
+/// The reader neither counts the blank lines it skips nor tells apart line breaks within quoted cells, so
+/// the line is counted from the byte offset of the record instead, which precedes any skipped blank lines.
+fn record_line(contents: &str, position: &Position) -> usize {
+    let offset = usize::try_from(position.byte())
+        .unwrap_or(usize::MAX)
+        .min(contents.len());
+    let (preceding, following) = contents.as_bytes().split_at(offset);
+    let blank_lines = following
+        .iter()
+        .take_while(|&&b| b == b'\r' || b == b'\n')
+        .filter(|&&b| b == b'\n')
+        .count();
+    preceding.iter().filter(|&&b| b == b'\n').count() + blank_lines + 1
+}
+
+/// Convert a single CSV row into an ical event
+fn read_row(
+    record: &StringRecord,
+    columns: &Columns,
+    mapping: &CsvMapping,
+    name: &str,
+) -> Result<IcalEvent> {
+    let cell = |column: Option<usize>| {
+        column
+            .and_then(|column| record.get(column))
+            .filter(|value| !value.is_empty())
+    };
+
+    let title = cell(Some(columns.title)).ok_or_else(|| eyre!("row has no title"))?;
+    let date_text = cell(Some(columns.date)).ok_or_else(|| eyre!("row has no date"))?;
+    let date = NaiveDate::parse_from_str(date_text, &mapping.date_format).wrap_err_with(|| {
+        format!(
+            "could not parse date {:?} with format {:?}",
+            date_text, mapping.date_format
+        )
+    })?;
+    let start_time = cell(columns.start_time)
+        .map(|time| parse_time(time, mapping))
+        .transpose()?;
+    let end_time = cell(columns.end_time)
+        .map(|time| parse_time(time, mapping))
+        .transpose()?;
+
+    let (start, end) = match (start_time, end_time) {
+        (Some(start_time), end_time) => {
+            let start = date.and_time(start_time);
+            let end = end_time.map(|end_time| {
+                let end = date.and_time(end_time);
+                if end < start {
+                    end + Duration::days(1)
+                } else {
+                    end
+                }
+            });
+            (
+                start.format(DATETIME_FORMAT).to_string(),
+                end.map(|end| end.format(DATETIME_FORMAT).to_string()),
+            )
+        }
+        (None, Some(_)) => bail!("row has an end time but no start time"),
+        (None, None) => (date.format("%Y-%m-%d").to_string(), None),
+    };
+
+    let uid = cell(columns.uid).map_or_else(|| derived_uid(&start, title, name), str::to_owned);
+
+    SourceEvent {
+        uid: Some(uid.clone()),
+        title: Some(title.to_owned()),
+        description: cell(columns.description).map(str::to_owned),
+        start: Some(SourceDate::Text(start)),
+        end: end.map(SourceDate::Text),
+        location: cell(columns.location).map(str::to_owned),
+        url: cell(columns.url).map(str::to_owned),
+        ..Default::default()
+    }
+    .to_ical_event(&uid)
+}
+
+fn parse_time(value: &str, mapping: &CsvMapping) -> Result<NaiveTime> {
+    match &mapping.time_format {
+        Some(format) => NaiveTime::parse_from_str(value, format)
+            .wrap_err_with(|| format!("could not parse time {:?} with format {:?}", value, format)),
+        None => TIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+            .ok_or_else(|| eyre!("could not parse time {:?}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::model::sources::test_util::property_value;
+
+    #[test]
+    fn reads_rows_and_reports_failures() {
+        let mapping = CsvMapping {
+            title: "Event".into(),
+            date: "Day".into(),
+            start_time: "From".into(),
+            end_time: "To".into(),
+            date_format: "%m/%d/%Y".into(),
+            ..Default::default()
+        };
+        let (calendar, failures) = read_calendar(
+            indoc! {r#"
+                Event,Day,From,To,Location,Notes
+                Spring Potluck,03/20/2024,6:00 PM,8:00 PM,"Hall 2, Main St",
+                Park Cleanup Day,03/23/2024,,,,
+                Late Show,3/22/2024,23:00,01:00,,
+                ,,,,,
+                Broken Date,2024-03-25,18:00,,,
+            "#},
+            "events",
+            &mapping,
+        )
+        .expect("could not read csv");
+
+        let events: Vec<_> = calendar
+            .events
+            .iter()
+            .map(|event| {
+                (
+                    property_value(event, "SUMMARY").unwrap(),
+                    property_value(event, "DTSTART").unwrap(),
+                    property_value(event, "DTEND"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("Spring Potluck", "20240320T180000", Some("20240320T200000")),
+                ("Park Cleanup Day", "20240323", None),
+                ("Late Show", "20240322T230000", Some("20240323T010000")),
+            ]
+        );
+        assert_eq!(
+            property_value(&calendar.events[0], "LOCATION"),
+            Some(r"Hall 2\, Main St")
+        );
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].context, "row 6");
+    }
+
+    #[test]
+    fn reports_failures_by_the_line_they_start_on() {
+        let (calendar, failures) = read_calendar(
+            indoc! {r#"
+                title,date,description
+                Spring Potluck,2024-03-20,"Bring a dish
+                to share"
+
+                Broken Date,03/25/2024,
+            "#},
+            "events",
+            &CsvMapping::default(),
+        )
+        .expect("could not read csv");
+
+        assert_eq!(calendar.events.len(), 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].context, "row 5");
+    }
+
+    #[test]
+    fn requires_title_and_date_columns() {
+        assert!(read_calendar("Name,When\n", "events", &CsvMapping::default()).is_err());
+    }
+}
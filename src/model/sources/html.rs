@@ -0,0 +1,499 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+use lol_html::{element, rewrite_str, text, Settings};
+use serde_json::Value;
+use std::{cell::RefCell, rc::Rc};
+use url::Url;
+
+use super::{derived_uid, FailedItem, SourceDate, SourceEvent};
+
+/// Microformats which may be nested within an h-event, e.g. a venue given as an h-card
+///
+/// Their properties belong to them rather than the event, so that a venue name is not taken for the event name.
+const NESTED_MICROFORMATS: &[&str] = &["h-card", "h-adr", "h-geo", "h-entry"];
+
+/// The parts of an address which are joined into the location of a JSON-LD event
+const ADDRESS_PARTS: &[&str] = &[
+    "streetAddress",
+    "addressLocality",
+    "addressRegion",
+    "postalCode",
+    "addressCountry",
+];
+
+/// Read the schema.org JSON-LD events and h-events of an HTML page into a calendar
+///
+/// Relative links are resolved against the `page_url` if it is known.
+/// Events which cannot be read are returned separately, identified by their kind and position in the page.
+pub(crate) fn read_calendar(
+    contents: &str,
+    name: &str,
+    page_url: Option<&Url>,
+) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let page = scan_page(contents)?;
+
+    let mut calendar = IcalCalendar::new();
+    calendar
+        .properties
+        .push(super::property("X-WR-CALNAME", name));
+    let mut failures = Vec::new();
+
+    let mut json_ld_events = Vec::new();
+    for (index, script) in page.json_ld.iter().enumerate() {
+        match serde_json::from_str::<Value>(script) {
+            Ok(value) => find_json_ld_events(value, &mut json_ld_events),
+            Err(error) => failures.push(FailedItem {
+                context: format!("JSON-LD script {}", index + 1),
+                error: error.into(),
+            }),
+        }
+    }
+
+    let events = json_ld_events
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (format!("JSON-LD event {}", index + 1), json_ld_event(item)))
+        .chain(
+            page.h_events
+                .iter()
+                .enumerate()
+                .map(|(index, item)| (format!("h-event {}", index + 1), item.to_source_event())),
+        );
+    for (context, event) in events {
+        match to_ical_event(event, name, page_url) {
+            Ok(event) => calendar.events.push(event),
+            Err(error) => failures.push(FailedItem { context, error }),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+/// The event data found in a page
+#[derive(Default)]
+struct Page {
+    /// The contents of each `application/ld+json` script
+    json_ld: Vec<String>,
+    h_events: Vec<HEvent>,
+    /// How many microformats nested within the current h-event are open
+    nested: usize,
+}
+
+/// The properties of an h-event as written in the page
+#[derive(Default)]
+struct HEvent {
+    uid: Option<String>,
+    uid_text: String,
+    name: String,
+    summary: String,
+    description: String,
+    start: Option<String>,
+    start_text: String,
+    end: Option<String>,
+    end_text: String,
+    location: String,
+    url: Option<String>,
+    photo: Option<String>,
+    categories: Vec<String>,
+}
+
+/// The h-event properties which are read from the text of their elements
+#[derive(Clone, Copy)]
+enum TextProperty {
+    Uid,
+    Name,
+    Summary,
+    Description,
+    Start,
+    End,
+    Location,
+    Category,
+}
+
+const TEXT_PROPERTIES: &[(&str, TextProperty)] = &[
+    (".h-event .u-uid", TextProperty::Uid),
+    (".h-event .p-name", TextProperty::Name),
+    (".h-event .p-summary", TextProperty::Summary),
+    (".h-event .p-description", TextProperty::Description),
+    (".h-event .e-content", TextProperty::Description),
+    (".h-event .dt-start", TextProperty::Start),
+    (".h-event .dt-end", TextProperty::End),
+    (".h-event .p-location", TextProperty::Location),
+    (".h-event .p-category", TextProperty::Category),
+];
+
+impl HEvent {
+    fn text_mut(&mut self, property: TextProperty) -> Option<&mut String> {
+        Some(match property {
+            TextProperty::Uid => &mut self.uid_text,
+            TextProperty::Name => &mut self.name,
+            TextProperty::Summary => &mut self.summary,
+            TextProperty::Description => &mut self.description,
+            TextProperty::Start => &mut self.start_text,
+            TextProperty::End => &mut self.end_text,
+            TextProperty::Location => &mut self.location,
+            TextProperty::Category => self.categories.last_mut()?,
+        })
+    }
+
+    fn to_source_event(&self) -> SourceEvent {
+        // the machine readable value of a date takes precedence over its text
+        let start = self
+            .start
+            .clone()
+            .or_else(|| non_empty(&self.start_text))
+            .map(SourceDate::Text);
+        let end = self
+            .end
+            .clone()
+            .or_else(|| non_empty(&self.end_text))
+            .map(SourceDate::Text);
+
+        SourceEvent {
+            uid: self.uid.clone().or_else(|| non_empty(&self.uid_text)),
+            title: non_empty(&self.name).or_else(|| non_empty(&self.summary)),
+            description: non_empty(&self.description),
+            start,
+            end,
+            location: non_empty(&self.location),
+            url: self.url.clone(),
+            image: self.photo.clone(),
+            categories: self
+                .categories
+                .iter()
+                .filter_map(|c| non_empty(c))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Collect the JSON-LD scripts and h-events of a page
+fn scan_page(contents: &str) -> Result<Page> {
+    let page = Rc::new(RefCell::new(Page::default()));
+
+    let mut handlers = vec![
+        element!(r#"script[type="application/ld+json"]"#, |_| {
+            page.borrow_mut().json_ld.push(String::new());
+            Ok(())
+        }),
+        // script contents are not HTML, so they are taken as they are
+        text!(r#"script[type="application/ld+json"]"#, |chunk| {
+            if let Some(script) = page.borrow_mut().json_ld.last_mut() {
+                script.push_str(chunk.as_str());
+            }
+            Ok(())
+        }),
+        element!(".h-event", |_| {
+            let mut page = page.borrow_mut();
+            page.h_events.push(HEvent::default());
+            page.nested = 0;
+            Ok(())
+        }),
+        element!(".h-event .dt-start", |el| {
+            if let Some(event) = page.borrow_mut().h_events.last_mut() {
+                event.start = event.start.take().or_else(|| value_attribute(el));
+            }
+            Ok(())
+        }),
+        element!(".h-event .dt-end", |el| {
+            if let Some(event) = page.borrow_mut().h_events.last_mut() {
+                event.end = event.end.take().or_else(|| value_attribute(el));
+            }
+            Ok(())
+        }),
+        element!(".h-event .u-uid", |el| {
+            let mut page = page.borrow_mut();
+            if page.nested == 0 {
+                if let Some(event) = page.h_events.last_mut() {
+                    event.uid = event.uid.take().or_else(|| link_attribute(el));
+                }
+            }
+            Ok(())
+        }),
+        element!(".h-event .u-url", |el| {
+            let mut page = page.borrow_mut();
+            if page.nested == 0 {
+                if let Some(event) = page.h_events.last_mut() {
+                    event.url = event.url.take().or_else(|| link_attribute(el));
+                }
+            }
+            Ok(())
+        }),
+        element!(".h-event .u-photo", |el| {
+            let mut page = page.borrow_mut();
+            if page.nested == 0 {
+                if let Some(event) = page.h_events.last_mut() {
+                    event.photo = event.photo.take().or_else(|| link_attribute(el));
+                }
+            }
+            Ok(())
+        }),
+        element!(".h-event .p-category", |_| {
+            let mut page = page.borrow_mut();
+            if page.nested == 0 {
+                if let Some(event) = page.h_events.last_mut() {
+                    event.categories.push(String::new());
+                }
+            }
+            Ok(())
+        }),
+    ];
+
+    for microformat in NESTED_MICROFORMATS {
+        let page = page.clone();
+        handlers.push(element!(format!(".h-event .{}", microformat), move |el| {
+            if let Some(end_tag_handlers) = el.end_tag_handlers() {
+                page.borrow_mut().nested += 1;
+                let page = page.clone();
+                end_tag_handlers.push(Box::new(move |_| {
+                    page.borrow_mut().nested -= 1;
+                    Ok(())
+                }));
+            }
+            Ok(())
+        }));
+    }
+
+    for (selector, property) in TEXT_PROPERTIES {
+        let page = page.clone();
+        handlers.push(text!(selector, move |chunk| {
+            let mut page = page.borrow_mut();
+            // the location may well be an h-card itself
+            let nested = page.nested > 0 && !matches!(property, TextProperty::Location);
+            if !nested {
+                if let Some(text) = page
+                    .h_events
+                    .last_mut()
+                    .and_then(|event| event.text_mut(*property))
+                {
+                    text.push_str(&decode_entities(chunk.as_str()));
+                }
+            }
+            Ok(())
+        }));
+    }
+
+    rewrite_str(
+        contents,
+        Settings {
+            element_content_handlers: handlers,
+            ..Default::default()
+        },
+    )
+    .wrap_err("could not read HTML page")?;
+
+    Ok(page.take())
+}
+
+/// The machine readable value of a date element, e.g. the `datetime` of a `<time>` element
+fn value_attribute(el: &lol_html::html_content::Element) -> Option<String> {
+    ["datetime", "value", "title", "content"]
+        .iter()
+        .find_map(|name| el.get_attribute(name))
+        .map(|value| decode_entities(&value))
+}
+
+/// The address an element links to, e.g. the `href` of an `<a>` or the `src` of an `<img>`
+fn link_attribute(el: &lol_html::html_content::Element) -> Option<String> {
+    ["href", "src", "data", "value"]
+        .iter()
+        .find_map(|name| el.get_attribute(name))
+        .map(|value| decode_entities(&value))
+}
+
+/// Find the schema.org events in a JSON-LD document, including those in `@graph` lists
+fn find_json_ld_events(value: Value, events: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                find_json_ld_events(item, events);
+            }
+        }
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                find_json_ld_events(graph, events);
+            }
+            if is_event_type(object.get("@type")) {
+                events.push(Value::Object(object));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether a JSON-LD `@type` is `Event` or one of its subtypes like `MusicEvent`
+fn is_event_type(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::String(name)) => name.ends_with("Event"),
+        Some(Value::Array(names)) => names.iter().any(|name| is_event_type(Some(name))),
+        _ => false,
+    }
+}
+
+fn json_ld_event(item: &Value) -> SourceEvent {
+    let field = |name: &str| item.get(name).and_then(json_text);
+
+    SourceEvent {
+        uid: field("@id").or_else(|| field("identifier")),
+        title: field("name"),
+        description: field("description"),
+        start: field("startDate").map(SourceDate::Text),
+        end: field("endDate").map(SourceDate::Text),
+        location: item.get("location").and_then(json_ld_location),
+        url: field("url"),
+        image: item.get("image").and_then(json_ld_link),
+        ..Default::default()
+    }
+}
+
+/// Describe a schema.org `Place` or `VirtualLocation` by its name and address
+fn json_ld_location(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(locations) => locations.iter().find_map(json_ld_location),
+        Value::Object(place) => {
+            let mut parts: Vec<String> =
+                place.get("name").and_then(json_text).into_iter().collect();
+            match place.get("address") {
+                Some(Value::Object(address)) => parts.extend(
+                    ADDRESS_PARTS
+                        .iter()
+                        .filter_map(|part| address.get(*part))
+                        .filter_map(|part| {
+                            json_text(part)
+                                .or_else(|| part.get("name")?.as_str().map(str::to_owned))
+                        }),
+                ),
+                Some(address) => parts.extend(json_text(address)),
+                None => {}
+            }
+            if parts.is_empty() {
+                parts.extend(place.get("url").and_then(json_text));
+            }
+            non_empty(&parts.join(", "))
+        }
+        value => json_text(value),
+    }
+}
+
+/// The address of a schema.org `ImageObject`, a list of them, or a plain link
+fn json_ld_link(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(links) => links.iter().find_map(json_ld_link),
+        Value::Object(object) => object
+            .get("url")
+            .or_else(|| object.get("contentUrl"))
+            .and_then(json_text),
+        value => json_text(value),
+    }
+}
+
+fn json_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => non_empty(text),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Convert an event found in a page into an ical event, resolving its links against the page
+fn to_ical_event(mut event: SourceEvent, name: &str, page_url: Option<&Url>) -> Result<IcalEvent> {
+    let Some(start) = &event.start else {
+        bail!("event has no start date");
+    };
+    let start = start.to_string();
+
+    if let Some(page_url) = page_url {
+        for link in [&mut event.url, &mut event.image].into_iter().flatten() {
+            if let Ok(resolved) = page_url.join(link) {
+                *link = resolved.to_string();
+            }
+        }
+    }
+
+    let uid = event
+        .uid
+        .clone()
+        .unwrap_or_else(|| derived_uid(&start, event.title.as_deref().unwrap_or_default(), name));
+    event.to_ical_event(&uid)
+}
+
+/// Collapse runs of whitespace and drop empty text
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Replace the character references of HTML text, e.g. `&amp;`, `&eacute;` or `&#8211;`
+fn decode_entities(text: &str) -> String {
+    html_escape::decode_html_entities(text).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::model::sources::test_util::property_value;
+
+    #[test]
+    fn reads_json_ld_and_h_events() {
+        let page_url = Url::parse("https://venue.example.com/events/").unwrap();
+        let (calendar, failures) = read_calendar(
+            include_str!("../../../tests/fixtures/venue-events.html"),
+            "venue",
+            Some(&page_url),
+        )
+        .expect("could not read page");
+
+        let events: Vec<_> = calendar
+            .events
+            .iter()
+            .map(|event| {
+                (
+                    property_value(event, "SUMMARY").unwrap_or_default(),
+                    property_value(event, "DTSTART").unwrap_or_default(),
+                    property_value(event, "LOCATION").unwrap_or_default(),
+                    property_value(event, "URL").unwrap_or_default(),
+                    property_value(event, "IMAGE").unwrap_or_default(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (
+                    "Jazz Night",
+                    "20240322T020000Z",
+                    r"The Blue Room\, 12 Main St\, Springfield",
+                    "https://venue.example.com/events/jazz-night",
+                    "https://venue.example.com/images/jazz.jpg",
+                ),
+                ("Poetry Slam", "20240328T190000", "Back Room", "", ""),
+                (
+                    "Open Mic & Karaoke",
+                    "20240329T020000Z",
+                    r"Café Luna\, 4 Elm St",
+                    "https://venue.example.com/events/open-mic",
+                    "https://venue.example.com/images/open-mic.png",
+                ),
+            ]
+        );
+
+        // one h-event has no start
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].context, "h-event 2");
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(
+            decode_entities("Rock &amp; Roll &#8211; Live &#x21; &bogus; & more"),
+            "Rock & Roll \u{2013} Live ! &bogus; & more"
+        );
+        assert_eq!(
+            decode_entities("Caf&eacute; Night &mdash; Rock&rsquo;n&hellip;"),
+            "Café Night \u{2014} Rock\u{2019}n\u{2026}"
+        );
+    }
+}
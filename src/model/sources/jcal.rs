@@ -0,0 +1,223 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use ical::parser::ical::component::{
+    IcalCalendar, IcalEvent, IcalTimeZone, IcalTimeZoneTransition, IcalTimeZoneTransitionType,
+};
+use ical::property::Property;
+use log::debug;
+use serde_json::{Map, Value};
+
+use super::{escape_text, FailedItem};
+
+/// Value types which iCalendar cannot infer from the property and need a `VALUE` parameter
+const EXPLICIT_VALUE_TYPES: &[&str] = &["date", "period", "binary"];
+
+/// Parse a jCal document into ical calendars along with the events of each which could not be read
+///
+/// A jCal document holds a single `vcalendar` component, but a list of them is accepted as well.
+pub(crate) fn parse_calendars(contents: &str) -> Result<Vec<(IcalCalendar, Vec<FailedItem>)>> {
+    let document: Value = serde_json::from_str(contents).wrap_err("could not parse jCal JSON")?;
+    match &document {
+        Value::Array(items) if items.first().is_some_and(Value::is_string) => {
+            Ok(vec![read_calendar(&document)?])
+        }
+        Value::Array(items) => items.iter().map(read_calendar).collect(),
+        _ => bail!("jCal document is not an array"),
+    }
+}
+
+/// A component as laid out in jCal: `[name, [properties...], [components...]]`
+struct Component<'a> {
+    name: String,
+    properties: &'a [Value],
+    components: &'a [Value],
+}
+
+impl<'a> Component<'a> {
+    fn new(value: &'a Value) -> Result<Component<'a>> {
+        match value.as_array().map(Vec::as_slice) {
+            Some([Value::String(name), Value::Array(properties), Value::Array(components)]) => {
+                Ok(Component {
+                    name: name.to_ascii_lowercase(),
+                    properties,
+                    components,
+                })
+            }
+            _ => bail!("jCal component is not an array of a name, properties and components"),
+        }
+    }
+
+    fn properties(&self) -> Result<Vec<Property>> {
+        self.properties
+            .iter()
+            .map(read_property)
+            .collect::<Result<_>>()
+            .wrap_err_with(|| format!("could not read properties of {}", self.name))
+    }
+}
+
+/// Convert a jCal `vcalendar` component into an ical calendar
+///
+/// Also used for xCal documents, which are converted into jCal first. Events which cannot be read are
+/// returned separately so that a single broken event does not take the rest of the calendar with it.
+pub(crate) fn read_calendar(value: &Value) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let component = Component::new(value)?;
+    if component.name != "vcalendar" {
+        bail!(
+            "expected a vcalendar component but found {}",
+            component.name
+        );
+    }
+
+    let mut calendar = IcalCalendar::new();
+    let mut failures = Vec::new();
+    calendar.properties = component.properties()?;
+    for (position, value) in component.components.iter().enumerate() {
+        let component = Component::new(value)?;
+        match component.name.as_str() {
+            "vevent" => match component.properties() {
+                Ok(properties) => {
+                    let mut event = IcalEvent::new();
+                    event.properties = properties;
+                    calendar.events.push(event);
+                }
+                Err(error) => failures.push(FailedItem {
+                    context: event_context(&component, position),
+                    error,
+                }),
+            },
+            "vtimezone" => {
+                let mut timezone = IcalTimeZone::new();
+                timezone.properties = component.properties()?;
+                for value in component.components {
+                    let observance = Component::new(value)?;
+                    let transition_type = match observance.name.as_str() {
+                        "standard" => IcalTimeZoneTransitionType::STANDARD,
+                        "daylight" => IcalTimeZoneTransitionType::DAYLIGHT,
+                        name => bail!("unexpected {} component in vtimezone", name),
+                    };
+                    let mut transition = IcalTimeZoneTransition::new(transition_type);
+                    transition.properties = observance.properties()?;
+                    timezone.transitions.push(transition);
+                }
+                calendar.timezones.push(timezone);
+            }
+            name => debug!("skipping unsupported {} component", name),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+/// Identify an event which could not be read by its UID, or its position in the calendar without one
+fn event_context(component: &Component, position: usize) -> String {
+    component
+        .properties
+        .iter()
+        .filter_map(Value::as_array)
+        .find(|property| {
+            property
+                .first()
+                .and_then(Value::as_str)
+                .is_some_and(|name| name.eq_ignore_ascii_case("uid"))
+        })
+        .and_then(|property| property.get(3))
+        .and_then(Value::as_str)
+        .map_or_else(
+            || format!("component {}", position + 1),
+            |uid| format!("event {}", uid),
+        )
+}
+
+/// Convert a jCal property, `[name, {parameters}, type, values...]`, into an ical property
+fn read_property(value: &Value) -> Result<Property> {
+    let Some(
+        [Value::String(name), Value::Object(parameters), Value::String(value_type), values @ ..],
+    ) = value.as_array().map(Vec::as_slice)
+    else {
+        bail!("jCal property is not an array of a name, parameters, a type and values");
+    };
+
+    let mut params: Vec<(String, Vec<String>)> = parameters
+        .iter()
+        .map(|(name, value)| {
+            let values = match value {
+                Value::Array(values) => values.iter().map(scalar_text).collect(),
+                value => vec![scalar_text(value)],
+            };
+            (name.to_ascii_uppercase(), values)
+        })
+        .collect();
+    if EXPLICIT_VALUE_TYPES.contains(&value_type.as_str()) {
+        params.push(("VALUE".to_owned(), vec![value_type.to_ascii_uppercase()]));
+    }
+
+    let values = values
+        .iter()
+        .map(|value| format_value(value_type, value))
+        .collect::<Result<Vec<_>>>()
+        .wrap_err_with(|| format!("could not read value of {}", name))?;
+
+    Ok(Property {
+        name: name.to_ascii_uppercase(),
+        params: (!params.is_empty()).then_some(params),
+        value: Some(values.join(",")),
+    })
+}
+
+/// Write a jCal value in its iCalendar text form
+fn format_value(value_type: &str, value: &Value) -> Result<String> {
+    Ok(match (value_type, value) {
+        ("period", Value::Array(parts)) => parts
+            .iter()
+            .map(|part| format_time(&scalar_text(part)))
+            .collect::<Vec<_>>()
+            .join("/"),
+        ("recur", Value::Object(parts)) => format_recur(parts),
+        // structured values like GEO or REQUEST-STATUS
+        (_, Value::Array(parts)) => parts
+            .iter()
+            .map(|part| format_value(value_type, part))
+            .collect::<Result<Vec<_>>>()?
+            .join(";"),
+        ("date" | "date-time", value) => format_time(&scalar_text(value)),
+        ("time" | "utc-offset", value) => scalar_text(value).replace(':', ""),
+        ("boolean", value) => scalar_text(value).to_ascii_uppercase(),
+        ("text", Value::String(text)) => escape_text(text),
+        (_, Value::Object(_)) => bail!("unexpected object for {} value", value_type),
+        (_, value) => scalar_text(value),
+    })
+}
+
+/// Write a date, date-time or period part like `2024-03-20T18:00:00Z` as `20240320T180000Z`
+fn format_time(value: &str) -> String {
+    value.replace(['-', ':'], "")
+}
+
+/// Write a jCal recurrence rule object as an `RRULE` value, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`
+fn format_recur(parts: &Map<String, Value>) -> String {
+    // FREQ is written first for the benefit of stricter parsers
+    let mut parts: Vec<(&String, &Value)> = parts.iter().collect();
+    parts.sort_by_key(|(name, _)| !name.eq_ignore_ascii_case("freq"));
+    parts
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Array(values) => {
+                    values.iter().map(scalar_text).collect::<Vec<_>>().join(",")
+                }
+                value if name.eq_ignore_ascii_case("until") => format_time(&scalar_text(value)),
+                value => scalar_text(value),
+            };
+            format!("{}={}", name.to_ascii_uppercase(), value)
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// The text of a string, number or boolean value
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,176 @@
+use chrono::{TimeZone, Utc};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+use serde_json::Value;
+
+use super::{derived_uid, FailedItem, SourceDate, SourceEvent};
+use crate::configuration::json_mapping::JsonMapping;
+
+/// Read a JSON document into a calendar with one event per item of its event array
+///
+/// Items which cannot be read are returned separately, identified by their JSON pointer.
+pub(crate) fn read_calendar(
+    contents: &str,
+    name: &str,
+    mapping: &JsonMapping,
+) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let document: Value = serde_json::from_str(contents).wrap_err("could not parse JSON")?;
+    let items = document
+        .pointer(&mapping.events)
+        .ok_or_else(|| eyre!("JSON has no events at {:?}", mapping.events))?
+        .as_array()
+        .ok_or_else(|| eyre!("JSON events at {:?} are not an array", mapping.events))?;
+
+    let mut calendar = IcalCalendar::new();
+    calendar
+        .properties
+        .push(super::property("X-WR-CALNAME", name));
+    let mut failures = Vec::new();
+
+    for (index, item) in items.iter().enumerate() {
+        match read_event(item, mapping, name) {
+            Ok(event) => calendar.events.push(event),
+            Err(error) => failures.push(FailedItem {
+                context: format!("event {}/{}", mapping.events, index),
+                error,
+            }),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+/// Convert a single JSON event into an ical event
+fn read_event(item: &Value, mapping: &JsonMapping, name: &str) -> Result<IcalEvent> {
+    if !item.is_object() {
+        bail!("event is not an object");
+    }
+    let field = |pointer: &str| item.pointer(pointer).and_then(text);
+
+    let start = item
+        .pointer(&mapping.start)
+        .map(date_text)
+        .transpose()?
+        .ok_or_else(|| eyre!("event has no start at {:?}", mapping.start))?;
+    let end = item.pointer(&mapping.end).map(date_text).transpose()?;
+    let title = field(&mapping.title);
+    let uid = field(&mapping.uid)
+        .unwrap_or_else(|| derived_uid(&start, title.as_deref().unwrap_or_default(), name));
+
+    let categories = match item.pointer(&mapping.categories) {
+        Some(Value::Array(categories)) => categories.iter().filter_map(text).collect(),
+        Some(category) => text(category).into_iter().collect(),
+        None => Vec::new(),
+    };
+
+    SourceEvent {
+        uid: Some(uid.clone()),
+        title,
+        description: field(&mapping.description),
+        start: Some(SourceDate::Text(start)),
+        end: end.map(SourceDate::Text),
+        timezone: field(&mapping.timezone),
+        location: field(&mapping.location),
+        url: field(&mapping.url),
+        categories,
+        ..Default::default()
+    }
+    .to_ical_event(&uid)
+}
+
+/// The text of a string, number, or boolean value, empty values have no text
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) if text.trim().is_empty() => None,
+        Value::String(text) => Some(text.to_owned()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// The text of a date value, converting Unix timestamps into UTC date-times
+fn date_text(value: &Value) -> Result<String> {
+    match value {
+        Value::Number(timestamp) => {
+            let seconds = timestamp
+                .as_i64()
+                .ok_or_else(|| eyre!("timestamp is not a whole number: {}", timestamp))?;
+            let datetime = Utc
+                .timestamp_opt(seconds, 0)
+                .single()
+                .ok_or_else(|| eyre!("timestamp is out of range: {}", seconds))?;
+            Ok(datetime.to_rfc3339())
+        }
+        Value::String(text) => Ok(text.to_owned()),
+        value => bail!("date is not a string or a timestamp: {}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::model::sources::test_util::property_value;
+
+    #[test]
+    fn maps_fields_and_reports_failures() {
+        let mapping = JsonMapping {
+            events: "/data/events".into(),
+            title: "/name".into(),
+            start: "/starts_at".into(),
+            end: "/ends_at".into(),
+            location: "/venue/name".into(),
+            categories: "/tags".into(),
+            ..Default::default()
+        };
+        let document = json!({
+            "data": {
+                "events": [
+                    {
+                        "id": 42,
+                        "name": "Spring Potluck",
+                        "starts_at": "2024-03-20T18:00:00-07:00",
+                        "ends_at": 1710986400,
+                        "venue": { "name": "Hall 2, Main St" },
+                        "tags": ["Food", "Community"]
+                    },
+                    {
+                        "name": "Park Cleanup Day",
+                        "starts_at": "2024-03-23",
+                        "tags": "Outdoors"
+                    },
+                    { "name": "No Start" }
+                ]
+            }
+        });
+        let (calendar, failures) =
+            read_calendar(&document.to_string(), "partner", &mapping).expect("could not read json");
+
+        assert_eq!(calendar.events.len(), 2);
+        let potluck = &calendar.events[0];
+        assert_eq!(property_value(potluck, "UID"), Some("42"));
+        assert_eq!(property_value(potluck, "DTSTART"), Some("20240321T010000Z"));
+        assert_eq!(property_value(potluck, "DTEND"), Some("20240321T020000Z"));
+        assert_eq!(
+            property_value(potluck, "LOCATION"),
+            Some(r"Hall 2\, Main St")
+        );
+        assert_eq!(
+            property_value(potluck, "CATEGORIES"),
+            Some("Food,Community")
+        );
+
+        let cleanup = &calendar.events[1];
+        assert_eq!(
+            property_value(cleanup, "UID"),
+            Some("2024-03-23-park-cleanup-day@partner")
+        );
+        assert_eq!(property_value(cleanup, "DTSTART"), Some("20240323"));
+        assert_eq!(property_value(cleanup, "CATEGORIES"), Some("Outdoors"));
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].context, "event /data/events/2");
+    }
+}
@@ -0,0 +1,181 @@
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use ical::parser::ical::component::{IcalCalendar, IcalEvent};
+use std::{fs, path::Path};
+
+use super::{FailedItem, SourceEvent};
+
+const TOML_DELIMITER: &str = "+++";
+const YAML_DELIMITER: &str = "---";
+
+/// Read a Markdown file with TOML or YAML front matter into an ical event
+///
+/// The `default_uid` is used if the front matter does not set a `uid`.
+pub(crate) fn read_event_file(path: &Path, default_uid: &str) -> Result<IcalEvent> {
+    let contents = fs::read_to_string(path).wrap_err("could not read markdown file")?;
+    parse_event(&contents, default_uid)
+}
+
+/// Parse the contents of a Markdown file into an ical event
+pub(crate) fn parse_event(contents: &str, default_uid: &str) -> Result<IcalEvent> {
+    let (front_matter, body) = split_front_matter(contents)?;
+    let mut event: SourceEvent = match front_matter {
+        FrontMatter::Toml(toml) => {
+            toml_edit::de::from_str(toml).wrap_err("could not parse TOML front matter")?
+        }
+        FrontMatter::Yaml(yaml) => {
+            serde_yaml::from_str(yaml).wrap_err("could not parse YAML front matter")?
+        }
+    };
+
+    let body = body.trim();
+    if event.description.is_none() && !body.is_empty() {
+        event.description = Some(body.to_owned());
+    }
+
+    event.to_ical_event(default_uid)
+}
+
+/// Read a directory of Markdown files into a calendar with one event per file
+///
+/// Files which cannot be read are returned separately, so they can be reported or cause the whole source to fail.
+pub(crate) fn read_calendar(dir: &Path, name: &str) -> Result<(IcalCalendar, Vec<FailedItem>)> {
+    let pattern = dir.join("**").join("*.md");
+    let mut calendar = IcalCalendar::new();
+    calendar
+        .properties
+        .push(super::property("X-WR-CALNAME", name));
+    let mut failures = Vec::new();
+
+    for entry in
+        glob::glob(&pattern.to_string_lossy()).wrap_err("could not search for markdown files")?
+    {
+        let path = entry.wrap_err("could not read markdown file path")?;
+        // files are identified by their path so that their events keep their UID between builds
+        let relative_path = path.strip_prefix(dir).unwrap_or(&path).with_extension("");
+        let default_uid = format!("{}@{}", relative_path.to_string_lossy(), name);
+
+        match read_event_file(&path, &default_uid) {
+            Ok(event) => calendar.events.push(event),
+            Err(error) => failures.push(FailedItem {
+                context: path.to_string_lossy().to_string(),
+                error,
+            }),
+        }
+    }
+
+    Ok((calendar, failures))
+}
+
+enum FrontMatter<'a> {
+    Toml(&'a str),
+    Yaml(&'a str),
+}
+
+/// Split a Markdown document into its front matter and its body
+fn split_front_matter(contents: &str) -> Result<(FrontMatter<'_>, &str)> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let first_line = contents.lines().next().unwrap_or_default().trim_end();
+    let delimiter = match first_line {
+        TOML_DELIMITER => TOML_DELIMITER,
+        YAML_DELIMITER => YAML_DELIMITER,
+        _ => bail!(
+            "markdown file does not start with {} or {} front matter",
+            TOML_DELIMITER,
+            YAML_DELIMITER
+        ),
+    };
+
+    let rest = &contents[first_line.len()..].trim_start_matches(['\r', '\n']);
+    let (front_matter, body) = rest
+        .split_once(&format!("\n{}", delimiter))
+        .or_else(|| rest.strip_prefix(delimiter).map(|body| ("", body)))
+        .ok_or_else(|| eyre!("front matter is not closed with {}", delimiter))?;
+    // drop the remainder of the closing delimiter line
+    let body = body.split_once('\n').map_or("", |(_, body)| body);
+
+    Ok((
+        if delimiter == TOML_DELIMITER {
+            FrontMatter::Toml(front_matter)
+        } else {
+            FrontMatter::Yaml(front_matter)
+        },
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::model::sources::test_util::property_value;
+
+    #[test]
+    fn parses_toml_front_matter() {
+        let event = parse_event(
+            indoc! {r#"
+                +++
+                title = "Spring Potluck"
+                start = 2024-03-20T18:00:00
+                end = "2024-03-20 20:00"
+                timezone = "America/Phoenix"
+                location = "Hall 2, Main St"
+                categories = ["Food", "Community"]
+                +++
+
+                Bring a dish to share!
+            "#},
+            "potluck@test",
+        )
+        .expect("could not parse event");
+
+        assert_eq!(property_value(&event, "UID"), Some("potluck@test"));
+        assert_eq!(property_value(&event, "SUMMARY"), Some("Spring Potluck"));
+        assert_eq!(property_value(&event, "DTSTART"), Some("20240320T180000"));
+        assert_eq!(property_value(&event, "DTEND"), Some("20240320T200000"));
+        assert_eq!(
+            property_value(&event, "LOCATION"),
+            Some(r"Hall 2\, Main St")
+        );
+        assert_eq!(property_value(&event, "CATEGORIES"), Some("Food,Community"));
+        assert_eq!(
+            property_value(&event, "DESCRIPTION"),
+            Some("Bring a dish to share!")
+        );
+    }
+
+    #[test]
+    fn parses_yaml_front_matter() {
+        let event = parse_event(
+            indoc! {"
+                ---
+                title: Board Meeting
+                uid: board-meeting
+                start: 2024-03-12
+                rrule: FREQ=MONTHLY;BYDAY=2TU
+                ---
+                Agenda to follow.
+            "},
+            "unused@test",
+        )
+        .expect("could not parse event");
+
+        assert_eq!(property_value(&event, "UID"), Some("board-meeting"));
+        assert_eq!(property_value(&event, "DTSTART"), Some("20240312"));
+        assert_eq!(
+            property_value(&event, "RRULE"),
+            Some("FREQ=MONTHLY;BYDAY=2TU")
+        );
+        assert_eq!(
+            property_value(&event, "DESCRIPTION"),
+            Some("Agenda to follow.")
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_front_matter() {
+        assert!(parse_event("# Just a heading\n", "x@test").is_err());
+        assert!(parse_event("+++\ntitle = \"No start\"\n+++\n", "x@test").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Calendar sources in formats other than iCalendar text
+//!
+//! Each source converts its input into ical components, so that its events are parsed by the same
+//! [`Event`](crate::model::event::Event) model as events from ICS files.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use color_eyre::eyre::{bail, Report, Result};
+use ical::parser::ical::component::IcalEvent;
+use ical::property::Property;
+use serde::Deserialize;
+use std::{fmt, path::Path};
+
+use crate::util::slugify;
+
+pub mod caldav;
+pub mod csv;
+pub mod html;
+pub mod jcal;
+pub mod json;
+pub mod markdown;
+pub mod xcal;
+
+/// Formats accepted for date-times without a UTC offset
+const NAIVE_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y%m%dT%H%M%S",
+];
+
+/// Formats accepted for dates of all-day events
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y%m%d"];
+
+/// The formats in which calendar files and feeds are accepted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CalendarFormat {
+    /// iCalendar text as defined by RFC 5545
+    Ics,
+    /// iCalendar data as JSON as defined by RFC 7265
+    Jcal,
+    /// iCalendar data as XML as defined by RFC 6321
+    Xcal,
+}
+
+impl CalendarFormat {
+    /// The format indicated by the extension of a file path or URL path, if any
+    pub(crate) fn from_extension(path: &str) -> Option<CalendarFormat> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ics" | "ical" | "ifb" => Some(CalendarFormat::Ics),
+            "jcal" | "json" => Some(CalendarFormat::Jcal),
+            "xcal" | "xcs" | "xml" => Some(CalendarFormat::Xcal),
+            _ => None,
+        }
+    }
+
+    /// The format indicated by the value of a `Content-Type` header, if any
+    pub(crate) fn from_content_type(content_type: &str) -> Option<CalendarFormat> {
+        let mime_type = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime_type.as_str() {
+            "text/calendar" => Some(CalendarFormat::Ics),
+            "application/calendar+json" | "application/json" => Some(CalendarFormat::Jcal),
+            "application/calendar+xml" | "application/xml" | "text/xml" => {
+                Some(CalendarFormat::Xcal)
+            }
+            _ => None,
+        }
+    }
+
+    /// The format of calendar data, using the content itself when there is no hint
+    ///
+    /// Feeds are often served as `text/plain` or `application/octet-stream` and cached files lose their
+    /// content type, so JSON and XML documents are recognized by their first character.
+    pub(crate) fn detect(hint: Option<CalendarFormat>, contents: &str) -> CalendarFormat {
+        hint.unwrap_or_else(|| {
+            match contents
+                .trim_start_matches('\u{feff}')
+                .trim_start()
+                .chars()
+                .next()
+            {
+                Some('[') => CalendarFormat::Jcal,
+                Some('<') => CalendarFormat::Xcal,
+                _ => CalendarFormat::Ics,
+            }
+        })
+    }
+}
+
+/// An item of a source which could not be converted into an event
+#[derive(Debug)]
+pub(crate) struct FailedItem {
+    /// Where the item was found, e.g. a file path or row number
+    pub context: String,
+    pub error: Report,
+}
+
+/// The details of a single event as written in a non-ICS source
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct SourceEvent {
+    pub uid: Option<String>,
+    #[serde(alias = "summary")]
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// A date like `2024-03-20` for all-day events, or a date-time like `2024-03-20 19:00`
+    pub start: Option<SourceDate>,
+    pub end: Option<SourceDate>,
+    /// The timezone of `start` and `end` unless they include a UTC offset
+    pub timezone: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    /// The address of an image for the event
+    pub image: Option<String>,
+    pub rrule: Option<String>,
+    #[serde(alias = "tags")]
+    pub categories: Vec<String>,
+}
+
+/// A date or date-time value, written either as a string or as a native TOML date-time
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SourceDate {
+    Text(String),
+    Toml(toml_edit::Datetime),
+}
+
+impl fmt::Display for SourceDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceDate::Text(text) => write!(f, "{}", text),
+            SourceDate::Toml(datetime) => write!(f, "{}", datetime),
+        }
+    }
+}
+
+impl SourceEvent {
+    /// Convert the event into an ical event
+    ///
+    /// The `default_uid` is used if the source does not provide a UID and should be stable between builds.
+    pub(crate) fn to_ical_event(&self, default_uid: &str) -> Result<IcalEvent> {
+        let Some(start) = &self.start else {
+            bail!("event has no start date");
+        };
+
+        let mut event = IcalEvent::new();
+        event
+            .properties
+            .push(property("UID", self.uid.as_deref().unwrap_or(default_uid)));
+        event.properties.push(time_property(
+            "DTSTART",
+            &start.to_string(),
+            self.timezone.as_deref(),
+        )?);
+        if let Some(end) = &self.end {
+            event.properties.push(time_property(
+                "DTEND",
+                &end.to_string(),
+                self.timezone.as_deref(),
+            )?);
+        }
+        if let Some(title) = &self.title {
+            event.properties.push(property("SUMMARY", title));
+        }
+        if let Some(description) = &self.description {
+            event
+                .properties
+                .push(property("DESCRIPTION", &escape_text(description)));
+        }
+        if let Some(location) = &self.location {
+            event
+                .properties
+                .push(property("LOCATION", &escape_text(location)));
+        }
+        if let Some(url) = &self.url {
+            event.properties.push(property("URL", url));
+        }
+        if let Some(image) = &self.image {
+            event.properties.push(Property {
+                name: "IMAGE".to_owned(),
+                params: Some(vec![("VALUE".to_owned(), vec!["URI".to_owned()])]),
+                value: Some(image.to_owned()),
+            });
+        }
+        if let Some(rrule) = &self.rrule {
+            event
+                .properties
+                .push(property("RRULE", rrule.trim_start_matches("RRULE:")));
+        }
+        if !self.categories.is_empty() {
+            let categories: Vec<String> = self.categories.iter().map(|c| escape_text(c)).collect();
+            event
+                .properties
+                .push(property("CATEGORIES", &categories.join(",")));
+        }
+
+        Ok(event)
+    }
+}
+
+/// Create an ical property without parameters
+pub(crate) fn property(name: &str, value: &str) -> Property {
+    Property {
+        name: name.to_owned(),
+        params: None,
+        value: Some(value.to_owned()),
+    }
+}
+
+/// Create a date or date-time ical property from a value written by a person, e.g. `2024-03-20 19:00`
+///
+/// Date-times with a UTC offset are converted to UTC, others are given in `timezone` if it is set
+/// and are floating otherwise.
+pub(crate) fn time_property(name: &str, value: &str, timezone: Option<&str>) -> Result<Property> {
+    let value = value.trim();
+
+    // web pages commonly leave out the seconds, e.g. `2024-03-20T19:00-07:00`
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z"))
+    {
+        return Ok(property(
+            name,
+            &datetime
+                .with_timezone(&Utc)
+                .format("%Y%m%dT%H%M%SZ")
+                .to_string(),
+        ));
+    }
+
+    if let Some(datetime) = NAIVE_DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    {
+        return Ok(Property {
+            name: name.to_owned(),
+            params: timezone.map(|tz| vec![("TZID".to_owned(), vec![tz.to_owned()])]),
+            value: Some(datetime.format("%Y%m%dT%H%M%S").to_string()),
+        });
+    }
+
+    if let Some(date) = DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+    {
+        return Ok(Property {
+            name: name.to_owned(),
+            params: Some(vec![("VALUE".to_owned(), vec!["DATE".to_owned()])]),
+            value: Some(date.format("%Y%m%d").to_string()),
+        });
+    }
+
+    bail!("could not parse {} value: {:?}", name, value)
+}
+
+/// Identify an event by its start and title for sources without identifiers
+///
+/// The identifier stays the same between builds as long as the event is not moved or renamed.
+pub(crate) fn derived_uid(start: &str, title: &str, calendar_name: &str) -> String {
+    format!("{}-{}@{}", slugify(start), slugify(title), calendar_name)
+}
+
+/// Escape a text value so that it reads back unchanged from an ical property
+pub(crate) fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace(',', r"\,")
+        .replace("\r\n", r"\n")
+        .replace('\n', r"\n")
+}
+
+/// Helpers shared by the tests of the sources
+#[cfg(test)]
+pub(crate) mod test_util {
+    use ical::parser::ical::component::IcalEvent;
+    use std::rc::Rc;
+
+    use crate::configuration::calendar_source_config::CalendarSourceConfig;
+
+    /// The value of the first property of an event with the given name
+    pub(crate) fn property_value<'a>(event: &'a IcalEvent, name: &str) -> Option<&'a str> {
+        event
+            .properties
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.value.as_deref())
+    }
+
+    /// The config of a red calendar source with the given extra settings in TOML
+    pub(crate) fn test_source_config(
+        name: &str,
+        source: &str,
+        settings: &str,
+    ) -> Rc<CalendarSourceConfig> {
+        Rc::new(
+            toml_edit::de::from_str(&format!(
+                "source = '{}'\nname = '{}'\ncolor = 'red'\n{}",
+                source, name, settings
+            ))
+            .expect("could not parse source config"),
+        )
+    }
+}
@@ -0,0 +1,183 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use ical::parser::ical::component::IcalCalendar;
+use roxmltree::{Document, Node};
+use serde_json::{json, Map, Value};
+
+use super::{jcal, FailedItem};
+
+/// Parse an xCal document into ical calendars along with the events of each which could not be read
+///
+/// xCal mirrors the structure of jCal, so the document is converted into jCal and read from there.
+pub(crate) fn parse_calendars(contents: &str) -> Result<Vec<(IcalCalendar, Vec<FailedItem>)>> {
+    let document = Document::parse(contents).wrap_err("could not parse xCal XML")?;
+    let root = document.root_element();
+    if root.tag_name().name() != "icalendar" {
+        bail!(
+            "expected an icalendar root element but found {}",
+            root.tag_name().name()
+        );
+    }
+
+    elements(root)
+        .map(|vcalendar| {
+            let (vcalendar, mut failures) = calendar_component(vcalendar)?;
+            let (calendar, event_failures) = jcal::read_calendar(&vcalendar)?;
+            failures.extend(event_failures);
+            Ok((calendar, failures))
+        })
+        .collect()
+}
+
+/// The child elements of a node, skipping text and comments
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+fn text(node: Node) -> String {
+    node.text().unwrap_or_default().to_owned()
+}
+
+/// Convert a `vcalendar` element into a jCal component, leaving out the events which cannot be converted
+fn calendar_component(node: Node) -> Result<(Value, Vec<FailedItem>)> {
+    let mut properties = Vec::new();
+    let mut components = Vec::new();
+    let mut failures = Vec::new();
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "properties" => {
+                properties = elements(child).map(property).collect::<Result<_>>()?;
+            }
+            "components" => {
+                for (position, child) in elements(child).enumerate() {
+                    match component(child) {
+                        Ok(component) => components.push(component),
+                        Err(error) if child.tag_name().name() == "vevent" => {
+                            failures.push(FailedItem {
+                                context: event_context(child, position),
+                                error,
+                            })
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+            element => bail!("unexpected {} element in vcalendar component", element),
+        }
+    }
+
+    Ok((
+        json!([node.tag_name().name(), properties, components]),
+        failures,
+    ))
+}
+
+/// Identify an event which could not be converted by its UID, or its position in the calendar without one
+fn event_context(node: Node, position: usize) -> String {
+    elements(node)
+        .filter(|child| child.tag_name().name() == "properties")
+        .flat_map(elements)
+        .find(|property| property.tag_name().name() == "uid")
+        .and_then(|uid| elements(uid).next())
+        .map_or_else(
+            || format!("component {}", position + 1),
+            |uid| format!("event {}", text(uid)),
+        )
+}
+
+/// Convert a component element into a jCal component
+fn component(node: Node) -> Result<Value> {
+    let name = node.tag_name().name();
+    let mut properties = Vec::new();
+    let mut components = Vec::new();
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "properties" => {
+                properties = elements(child).map(property).collect::<Result<_>>()?;
+            }
+            "components" => {
+                components = elements(child).map(component).collect::<Result<_>>()?;
+            }
+            element => bail!("unexpected {} element in {} component", element, name),
+        }
+    }
+
+    Ok(json!([name, properties, components]))
+}
+
+/// Convert a property element into a jCal property
+fn property(node: Node) -> Result<Value> {
+    let name = node.tag_name().name();
+
+    // these properties hold their structured values directly instead of in value elements
+    match name {
+        "geo" => {
+            let values: Vec<String> = elements(node).map(text).collect();
+            return Ok(json!([name, {}, "float", values]));
+        }
+        "request-status" => {
+            let values: Vec<String> = elements(node).map(text).collect();
+            return Ok(json!([name, {}, "text", values]));
+        }
+        _ => {}
+    }
+
+    let mut parameters = Map::new();
+    let mut value_type = None;
+    let mut values = Vec::new();
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "parameters" => {
+                for parameter in elements(child) {
+                    let mut parameter_values: Vec<Value> = elements(parameter)
+                        .map(|v| Value::String(text(v)))
+                        .collect();
+                    let parameter_value = if parameter_values.len() == 1 {
+                        parameter_values.remove(0)
+                    } else {
+                        Value::Array(parameter_values)
+                    };
+                    parameters.insert(parameter.tag_name().name().to_owned(), parameter_value);
+                }
+            }
+            child_type => {
+                value_type.get_or_insert(child_type);
+                values.push(value(child));
+            }
+        }
+    }
+
+    let Some(value_type) = value_type else {
+        bail!("{} property has no value", name);
+    };
+    let mut property = vec![json!(name), Value::Object(parameters), json!(value_type)];
+    property.extend(values);
+    Ok(Value::Array(property))
+}
+
+/// Convert a value element into a jCal value
+fn value(node: Node) -> Value {
+    match node.tag_name().name() {
+        // parts like <byday> may be repeated and become lists
+        "recur" => {
+            let mut parts = Map::new();
+            for part in elements(node) {
+                let name = part.tag_name().name().to_owned();
+                let value = Value::String(text(part));
+                match parts.get_mut(&name) {
+                    Some(Value::Array(values)) => values.push(value),
+                    Some(existing) => *existing = json!([existing.take(), value]),
+                    None => {
+                        parts.insert(name, value);
+                    }
+                }
+            }
+            Value::Object(parts)
+        }
+        "period" => Value::Array(
+            elements(node)
+                .map(|part| Value::String(text(part)))
+                .collect(),
+        ),
+        _ => Value::String(text(node)),
+    }
+}
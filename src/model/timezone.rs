@@ -0,0 +1,405 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{Tz as ChronoTz, TZ_VARIANTS};
+use ical::parser::ical::component::IcalTimeZone;
+use ical::property::Property;
+use log::{debug, warn};
+use rrule::{RRule, Tz as RruleTz, Unvalidated};
+use std::collections::HashMap;
+
+use super::windows_timezones::WINDOWS_TIMEZONES;
+
+const VTIMEZONE_DTSTART_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Resolves the `TZID` parameters of a calendar into timezones
+///
+/// A `TZID` may be an IANA timezone name, a Windows timezone name as written by Outlook and Exchange,
+/// or the identifier of a `VTIMEZONE` component defined in the calendar itself.
+#[derive(Debug)]
+pub struct TimeZoneResolver {
+    /// Timezones defined by the `VTIMEZONE` components of the calendar, keyed by their `TZID`
+    vtimezones: HashMap<String, ChronoTz>,
+    /// The timezone used for date-times which have no `TZID`
+    default_timezone: ChronoTz,
+    /// The timezone used when a `TZID` cannot be resolved
+    fallback_timezone: ChronoTz,
+}
+
+/// A `STANDARD` or `DAYLIGHT` sub-component of a `VTIMEZONE`
+struct Observance {
+    /// The local times at which this observance takes effect
+    onsets: Vec<NaiveDateTime>,
+    /// The UTC offset in seconds in effect before each onset
+    offset_from: i32,
+    /// The UTC offset in seconds in effect after each onset
+    offset_to: i32,
+}
+
+impl TimeZoneResolver {
+    pub fn new(
+        timezones: &[IcalTimeZone],
+        default_timezone: ChronoTz,
+        fallback_timezone: ChronoTz,
+    ) -> TimeZoneResolver {
+        let vtimezones = timezones
+            .iter()
+            .filter_map(|vtimezone| {
+                let tzid = property_value(&vtimezone.properties, "TZID")?;
+                match resolve_vtimezone(tzid, vtimezone) {
+                    Some(tz) => {
+                        debug!("resolved VTIMEZONE {:?} to {}", tzid, tz);
+                        Some((tzid.to_owned(), tz))
+                    }
+                    None => {
+                        warn!("could not match VTIMEZONE {:?} to a known timezone", tzid);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        TimeZoneResolver {
+            vtimezones,
+            default_timezone,
+            fallback_timezone,
+        }
+    }
+
+    /// The timezone for date-times which do not specify a `TZID`
+    pub fn default_timezone(&self) -> ChronoTz {
+        self.default_timezone
+    }
+
+    /// Resolve the value of a `TZID` parameter into a timezone
+    ///
+    /// Unknown timezones produce a warning and resolve to the fallback timezone.
+    pub fn resolve(&self, tzid: &str) -> ChronoTz {
+        let tzid = tzid.trim_matches('"');
+        self.vtimezones
+            .get(tzid)
+            .copied()
+            .or_else(|| lookup_timezone_name(tzid))
+            .unwrap_or_else(|| {
+                warn!(
+                    "unknown timezone {:?}, using fallback timezone {}",
+                    tzid, self.fallback_timezone
+                );
+                self.fallback_timezone
+            })
+    }
+}
+
+/// Look up a timezone by its IANA or Windows name
+///
+/// IANA names embedded in longer identifiers, e.g. `/mozilla.org/20050126_1/America/New_York`, are found as well.
+pub fn lookup_timezone_name(name: &str) -> Option<ChronoTz> {
+    if let Ok(tz) = name.parse::<ChronoTz>() {
+        return Some(tz);
+    }
+
+    if let Some((_, iana_name)) = WINDOWS_TIMEZONES
+        .iter()
+        .find(|(windows_name, _)| windows_name.eq_ignore_ascii_case(name))
+    {
+        return iana_name.parse().ok();
+    }
+
+    // try successively shorter suffixes of a path-like identifier
+    let segments: Vec<&str> = name.split('/').collect();
+    (1..segments.len()).find_map(|i| segments[i..].join("/").parse().ok())
+}
+
+/// Find the timezone described by a `VTIMEZONE` component
+///
+/// Custom `TZID`s are matched against known timezones by comparing the UTC offsets which the
+/// `VTIMEZONE` rules produce from the previous through the next year. Offsets are compared in the
+/// middle of every month and right around every transition, so that zones with the same offsets
+/// but other daylight saving time rules do not match.
+fn resolve_vtimezone(tzid: &str, vtimezone: &IcalTimeZone) -> Option<ChronoTz> {
+    if let Some(tz) = property_value(&vtimezone.properties, "X-LIC-LOCATION")
+        .and_then(lookup_timezone_name)
+        .or_else(|| lookup_timezone_name(tzid))
+    {
+        return Some(tz);
+    }
+
+    let observances: Vec<Observance> = vtimezone
+        .transitions
+        .iter()
+        .filter_map(|transition| Observance::new(&transition.properties))
+        .collect();
+    if observances.is_empty() {
+        return None;
+    }
+
+    let this_year = Utc::now().year();
+    let years = this_year - 1..=this_year + 1;
+    let mid_months = years.clone().flat_map(|year| {
+        (1..=12).filter_map(move |month| {
+            NaiveDate::from_ymd_opt(year, month, 15)?.and_hms_opt(12, 0, 0)
+        })
+    });
+    let transitions = observances
+        .iter()
+        .flat_map(|observance| {
+            observance
+                .onsets
+                .iter()
+                .map(|onset| *onset - Duration::seconds(observance.offset_from.into()))
+        })
+        .filter(|transition| years.contains(&transition.year()))
+        .flat_map(|transition| {
+            [
+                transition - Duration::minutes(1),
+                transition + Duration::minutes(1),
+            ]
+        });
+    let samples: Vec<(NaiveDateTime, i32)> = mid_months
+        .chain(transitions)
+        .filter_map(|sample| Some((sample, utc_offset_at(&observances, sample)?)))
+        .collect();
+
+    TZ_VARIANTS.iter().copied().find(|tz| {
+        samples.iter().all(|(sample, offset)| {
+            tz.offset_from_utc_datetime(sample).fix().local_minus_utc() == *offset
+        })
+    })
+}
+
+/// Returns the UTC offset in seconds which a set of observances prescribe at a given UTC time
+fn utc_offset_at(observances: &[Observance], utc_time: NaiveDateTime) -> Option<i32> {
+    observances
+        .iter()
+        .flat_map(|observance| {
+            observance.onsets.iter().map(|onset| {
+                (
+                    *onset - Duration::seconds(observance.offset_from.into()),
+                    observance.offset_to,
+                )
+            })
+        })
+        .filter(|(onset, _)| *onset <= utc_time)
+        .max_by_key(|(onset, _)| *onset)
+        .map(|(_, offset)| offset)
+}
+
+impl Observance {
+    fn new(properties: &[Property]) -> Option<Observance> {
+        let dt_start = NaiveDateTime::parse_from_str(
+            property_value(properties, "DTSTART")?,
+            VTIMEZONE_DTSTART_FORMAT,
+        )
+        .ok()?;
+        let offset_from = parse_utc_offset(property_value(properties, "TZOFFSETFROM")?)?;
+        let offset_to = parse_utc_offset(property_value(properties, "TZOFFSETTO")?)?;
+
+        let mut onsets = vec![dt_start];
+
+        // the rule is expanded in UTC since onsets are local wall-clock times
+        if let Some(rrule) = property_value(properties, "RRULE") {
+            let end_of_next_year =
+                NaiveDate::from_ymd_opt(Utc::now().year() + 1, 12, 31)?.and_hms_opt(0, 0, 0)?;
+            let rrule_start: DateTime<RruleTz> = RruleTz::UTC.from_utc_datetime(&dt_start);
+            match rrule
+                .parse::<RRule<Unvalidated>>()
+                .and_then(|rrule| rrule.build(rrule_start))
+            {
+                Ok(rrule_set) => onsets.extend(
+                    rrule_set
+                        .before(RruleTz::UTC.from_utc_datetime(&end_of_next_year))
+                        .all(u16::MAX)
+                        .dates
+                        .iter()
+                        .map(|onset| onset.naive_utc()),
+                ),
+                Err(e) => warn!("could not parse VTIMEZONE rule {:?}: {}", rrule, e),
+            }
+        }
+
+        for rdate in properties.iter().filter(|p| p.name == "RDATE") {
+            onsets.extend(
+                rdate
+                    .value
+                    .iter()
+                    .flat_map(|v| v.split(','))
+                    .filter_map(|v| {
+                        NaiveDateTime::parse_from_str(v, VTIMEZONE_DTSTART_FORMAT).ok()
+                    }),
+            );
+        }
+
+        Some(Observance {
+            onsets,
+            offset_from,
+            offset_to,
+        })
+    }
+}
+
+/// Parse a UTC offset like `-0500` or `+053000` into seconds
+fn parse_utc_offset(value: &str) -> Option<i32> {
+    let (sign, digits) = match value.split_at(1) {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let hours: i32 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    let seconds: i32 = digits.get(4..6).map_or(Some(0), |s| s.parse().ok())?;
+
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+fn property_value<'a>(properties: &'a [Property], name: &str) -> Option<&'a str> {
+    properties
+        .iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.value.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use ical::IcalParser;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn windows_timezones_are_valid_iana_names() {
+        for (windows_name, iana_name) in WINDOWS_TIMEZONES {
+            assert!(
+                iana_name.parse::<ChronoTz>().is_ok(),
+                "{} maps to unknown timezone {}",
+                windows_name,
+                iana_name
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_windows_and_embedded_names() {
+        let resolver = TimeZoneResolver::new(&[], chrono_tz::UTC, chrono_tz::UTC);
+        assert_eq!(
+            resolver.resolve("Eastern Standard Time"),
+            chrono_tz::America::New_York
+        );
+        assert_eq!(
+            resolver.resolve("/mozilla.org/20050126_1/Europe/Berlin"),
+            chrono_tz::Europe::Berlin
+        );
+        assert_eq!(
+            resolver.resolve("Not A Real Zone"),
+            chrono_tz::UTC,
+            "unknown zones should use the fallback"
+        );
+    }
+
+    /// The UTC offsets in seconds of a timezone right before and after 01:00 UTC on the last Sunday of March
+    fn offsets_around_march_transition(tz: ChronoTz) -> (i32, i32) {
+        let end_of_march = NaiveDate::from_ymd_opt(Utc::now().year(), 3, 31).unwrap();
+        let last_sunday =
+            end_of_march - Duration::days(end_of_march.weekday().num_days_from_sunday().into());
+        let offset_at = |hour, minute| {
+            tz.offset_from_utc_datetime(&last_sunday.and_hms_opt(hour, minute, 0).unwrap())
+                .fix()
+                .local_minus_utc()
+        };
+        (offset_at(0, 30), offset_at(1, 30))
+    }
+
+    #[test]
+    fn distinguishes_vtimezones_with_same_offsets_but_other_rules() {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VTIMEZONE
+            TZID:Custom Eastern European Time
+            BEGIN:STANDARD
+            DTSTART:19701025T040000
+            TZOFFSETFROM:+0300
+            TZOFFSETTO:+0200
+            RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU
+            END:STANDARD
+            BEGIN:DAYLIGHT
+            DTSTART:19700329T030000
+            TZOFFSETFROM:+0200
+            TZOFFSETTO:+0300
+            RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU
+            END:DAYLIGHT
+            END:VTIMEZONE
+            BEGIN:VTIMEZONE
+            TZID:Custom Levant Time
+            BEGIN:STANDARD
+            DTSTART:19701025T000000
+            TZOFFSETFROM:+0300
+            TZOFFSETTO:+0200
+            RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU
+            END:STANDARD
+            BEGIN:DAYLIGHT
+            DTSTART:19700329T000000
+            TZOFFSETFROM:+0200
+            TZOFFSETTO:+0300
+            RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU
+            END:DAYLIGHT
+            END:VTIMEZONE
+            END:VCALENDAR
+        "};
+        let calendar = IcalParser::new(ics.as_bytes())
+            .next()
+            .expect("no calendar found")
+            .expect("could not parse calendar");
+        let resolver = TimeZoneResolver::new(&calendar.timezones, chrono_tz::UTC, chrono_tz::UTC);
+
+        // both zones agree in the middle of every month, but switch at different times
+        assert_eq!(
+            offsets_around_march_transition(resolver.resolve("Custom Eastern European Time")),
+            (7200, 10800)
+        );
+        assert_eq!(
+            offsets_around_march_transition(resolver.resolve("Custom Levant Time")),
+            (10800, 10800)
+        );
+    }
+
+    #[test]
+    fn resolves_custom_vtimezone_by_offsets() {
+        let ics = indoc! {"
+            BEGIN:VCALENDAR
+            BEGIN:VTIMEZONE
+            TZID:Custom Southern Time
+            BEGIN:STANDARD
+            DTSTART:16010101T030000
+            TZOFFSETFROM:+1100
+            TZOFFSETTO:+1000
+            RRULE:FREQ=YEARLY;BYMONTH=4;BYDAY=1SU
+            END:STANDARD
+            BEGIN:DAYLIGHT
+            DTSTART:16010101T020000
+            TZOFFSETFROM:+1000
+            TZOFFSETTO:+1100
+            RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=1SU
+            END:DAYLIGHT
+            END:VTIMEZONE
+            END:VCALENDAR
+        "};
+        let calendar = IcalParser::new(ics.as_bytes())
+            .next()
+            .expect("no calendar found")
+            .expect("could not parse calendar");
+        let resolver = TimeZoneResolver::new(&calendar.timezones, chrono_tz::UTC, chrono_tz::UTC);
+        let tz = resolver.resolve("Custom Southern Time");
+
+        // any zone following the same rules is acceptable, so compare the offsets
+        for month in [1, 7] {
+            let sample = NaiveDate::from_ymd_opt(Utc::now().year(), month, 15)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap();
+            assert_eq!(
+                tz.offset_from_utc_datetime(&sample).fix(),
+                chrono_tz::Australia::Sydney
+                    .offset_from_utc_datetime(&sample)
+                    .fix()
+            );
+        }
+    }
+}
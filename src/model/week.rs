@@ -157,7 +157,7 @@ impl Week<'_> {
         DateRule::daily(self.first_day()).with_count(7)
     }
 
-    pub fn format<'a>(&'a self, fmt: &'a str) -> DelayedFormat<StrftimeItems<'_>> {
+    pub fn format<'a>(&'a self, fmt: &'a str) -> DelayedFormat<StrftimeItems<'a>> {
         self.first_day().format(fmt)
     }
 
@@ -36,6 +36,35 @@ pub fn delete_dir_contents<P: AsRef<Path>>(path: P) {
     }
 }
 
+/// Hash a list of strings with 64 bit FNV-1a
+///
+/// Unlike [`std::collections::hash_map::DefaultHasher`] the output of this function is guaranteed not to change between
+/// releases, so it can be used for identifiers which must stay the same between builds, e.g. file names.
+pub fn stable_hash(parts: &[&str]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    parts
+        .iter()
+        // separate the parts so that e.g. ["ab", "c"] and ["a", "bc"] hash differently
+        .flat_map(|part| part.bytes().chain(std::iter::once(0)))
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Turn a string into a lowercase, dash separated identifier which is safe for use in paths and CSS classes
+///
+/// e.g. `"Workshops & Talks"` becomes `"workshops-talks"`
+pub fn slugify(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 pub fn restore_missing_templates(path: &Path) -> Result<()> {
     debug!("creating templates path: {:?}", path);
     fs::create_dir_all(path).wrap_err("could not create templates path")?;
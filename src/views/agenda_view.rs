@@ -140,7 +140,7 @@ impl AgendaView<'_> {
         context.insert("month_view_path", &first_event.month_view_path());
         context.insert("week_view_path", &first_event.week_view_path());
         context.insert("day_view_path", &first_event.day_view_path());
-        context.insert("event_view_path", &first_event.file_path());
+        context.insert("event_view_path", &first_event.page_path(self.config()));
         // context.insert("agenda_view_path", &base_url_path.join("agenda"));
 
         context.insert("current_view", VIEW_PATH);
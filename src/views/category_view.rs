@@ -0,0 +1,220 @@
+use color_eyre::eyre::Result;
+use std::{
+    collections::BTreeMap,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::feed_view::write_feed;
+use crate::{
+    configuration::config::Config,
+    model::{
+        calendar_collection::CalendarCollection,
+        event::{Event, EventContext},
+    },
+    util::{slugify, stable_hash},
+};
+
+pub(crate) const VIEW_PATH: &str = "category";
+const PAGE_TITLE: &str = "Category Page";
+
+/// Characters which only separate the words of a category name and may be left out of its slug
+const SEPARATORS: &[char] = &[
+    '-', '_', '.', ',', ';', ':', '/', '&', '\'', '"', '(', ')', '!', '?',
+];
+
+/// Events of a single category, keyed by the category slug
+type EventsByCategory = BTreeMap<String, (String, Vec<Rc<Event>>)>;
+
+#[derive(Debug)]
+pub(crate) struct CategoryView<'a> {
+    calendars: &'a CalendarCollection,
+    output_dir: PathBuf,
+}
+
+impl CategoryView<'_> {
+    pub fn new(calendars: &CalendarCollection) -> CategoryView<'_> {
+        let output_dir = calendars
+            .base_dir()
+            .join(&calendars.config.output_dir)
+            .join(VIEW_PATH);
+        CategoryView {
+            calendars,
+            output_dir,
+        }
+    }
+
+    fn config(&self) -> &Config {
+        &self.calendars.config
+    }
+
+    fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Writes an agenda page and an ICS feed for every category, plus an index of all categories
+    pub fn create_html_pages(&self) -> Result<()> {
+        // create the subdirectory to hold the files
+        create_dir_all(self.output_dir())?;
+
+        let mut events_by_category = EventsByCategory::new();
+        for event in self.calendars.events() {
+            for category in event.categories() {
+                events_by_category
+                    .entry(category_slug(category))
+                    .or_insert_with(|| (category.clone(), Vec::new()))
+                    .1
+                    .push(event.clone());
+            }
+        }
+
+        for (slug, (name, events)) in events_by_category.iter_mut() {
+            events.sort();
+            self.write_category(slug, name, events)?;
+        }
+
+        self.write_index(&events_by_category)
+    }
+
+    fn write_category(&self, slug: &str, name: &str, events: &[Rc<Event>]) -> Result<()> {
+        let category_dir = self.output_dir().join(slug);
+        create_dir_all(&category_dir)?;
+
+        let mut context = self.calendars.template_context();
+        context.insert("current_view", VIEW_PATH);
+        context.insert("page_title", PAGE_TITLE);
+        context.insert("category_name", name);
+        context.insert("category_slug", slug);
+        context.insert(
+            "category_feed_path",
+            &format!("{}feed.ics", category_path(self.config(), name)),
+        );
+        context.insert(
+            "events",
+            &events
+                .iter()
+                .map(|e| e.context(self.config()))
+                .collect::<Vec<EventContext>>(),
+        );
+
+        self.calendars.write_template(
+            "category.html",
+            &context,
+            &category_dir.join("index.html"),
+        )?;
+
+        if self.config().render_feed {
+            write_feed(
+                self.calendars,
+                &format!("statical feed: {}", name),
+                &format!("events in the {} category", name),
+                events.iter(),
+                &category_dir.join("feed.ics"),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn write_index(&self, events_by_category: &EventsByCategory) -> Result<()> {
+        let categories: Vec<BTreeMap<&str, String>> = events_by_category
+            .values()
+            .map(|(name, events)| {
+                BTreeMap::from([
+                    ("name", name.clone()),
+                    ("path", category_path(self.config(), name)),
+                    ("event_count", events.len().to_string()),
+                ])
+            })
+            .collect();
+
+        let mut context = self.calendars.template_context();
+        context.insert("current_view", VIEW_PATH);
+        context.insert("page_title", PAGE_TITLE);
+        context.insert("categories", &categories);
+
+        self.calendars.write_template(
+            "categories.html",
+            &context,
+            &self.output_dir().join("index.html"),
+        )
+    }
+}
+
+/// Returns the identifier of a category for use in paths and CSS classes
+///
+/// Names which differ only in case or separators share a slug. Other characters such as `+` or `#` are
+/// left out of the slug as well, so a hash of the name is added to keep e.g. "C++" apart from "C".
+/// Names without any letters or digits are identified by the hash alone.
+pub(crate) fn category_slug(name: &str) -> String {
+    let slug = slugify(name);
+    let significant =
+        |c: char| !(c.is_alphanumeric() || c.is_whitespace() || SEPARATORS.contains(&c));
+    if slug.is_empty() {
+        format!("{:016x}", stable_hash(&[name]))
+    } else if name.contains(significant) {
+        format!("{}-{:08x}", slug, stable_hash(&[name]) as u32)
+    } else {
+        slug
+    }
+}
+
+/// Returns the path of the page listing the events of a category
+pub(crate) fn category_path(config: &Config, name: &str) -> String {
+    let base_url_path: unix_path::PathBuf = config.base_url_path.path_buf().clone();
+    base_url_path
+        .join(VIEW_PATH)
+        .join(category_slug(name))
+        .join("")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn symbol_only_categories_get_their_own_pages() {
+        let star = category_slug("★");
+        let plus = category_slug("+");
+
+        assert!(!star.is_empty() && !plus.is_empty());
+        assert_ne!(star, plus);
+        assert_ne!(category_path(&Config::default(), "★"), "/category/");
+    }
+
+    #[test]
+    fn colliding_categories_get_distinct_slugs() {
+        assert_eq!(category_slug("C"), "c");
+        assert_eq!(category_slug("Workshops & Talks"), "workshops-talks");
+
+        let slugs = [
+            category_slug("C"),
+            category_slug("C++"),
+            category_slug("C#"),
+        ];
+        assert!(slugs[1].starts_with("c-") && slugs[2].starts_with("c-"));
+        assert_ne!(slugs[0], slugs[1]);
+        assert_ne!(slugs[1], slugs[2]);
+    }
+
+    #[test]
+    fn category_paths_start_with_the_base_url_path() {
+        let config = Config {
+            base_url_path: "/events/".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            category_path(&config, "Workshops & Talks"),
+            "/events/category/workshops-talks/"
+        );
+        assert_eq!(
+            category_path(&Config::default(), "Workshops & Talks"),
+            "/category/workshops-talks/"
+        );
+    }
+}
@@ -133,7 +133,10 @@ impl DayView<'_> {
         context.insert("month_view_path", &current_day.month_view_path());
         context.insert("week_view_path", &current_day.week_view_path());
         // context.insert("day_view_path", &current_day.day_view_path());
-        context.insert("event_view_path", &events.first().map(|e| e.file_path()));
+        context.insert(
+            "event_view_path",
+            &events.first().map(|e| e.page_path(self.config())),
+        );
         // context.insert("agenda_view_path", &base_url_path.join("agenda"));
 
         context.insert("current_view", VIEW_PATH);
@@ -7,7 +7,10 @@ use std::{
 
 use crate::{
     configuration::config::Config,
-    model::{calendar_collection::CalendarCollection, event::Event},
+    model::{
+        calendar_collection::CalendarCollection,
+        event::{Event, EventContext},
+    },
 };
 
 /// A triple with the previous, current, and next events present
@@ -124,6 +127,19 @@ impl EventView<'_> {
         context.insert("day", &current_event.day());
         // TODO switch these to contexts
         context.insert("event", &current_event.context(self.config()));
+        // series pages list the dates of their occurrences, which may come from RDATEs alone
+        let is_series_page =
+            self.config().render_series_pages && !current_event.occurrences().is_empty();
+        let occurrences: Vec<EventContext> = if is_series_page {
+            self.calendars
+                .upcoming_occurrences(current_event)
+                .iter()
+                .map(|o| o.context(self.config()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        context.insert("occurrences", &occurrences);
 
         let base_url_path: unix_path::PathBuf =
             self.calendars.config.base_url_path.path_buf().clone();
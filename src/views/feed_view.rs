@@ -4,9 +4,10 @@ use std::{
     fs::{create_dir_all, File},
     io::Write,
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
-use crate::model::calendar_collection::CalendarCollection;
+use crate::model::{calendar_collection::CalendarCollection, event::Event as StaticalEvent};
 
 pub(crate) const VIEW_PATH: &str = "feed";
 
@@ -36,38 +37,67 @@ impl FeedView<'_> {
         // create the subdirectory to hold the files
         create_dir_all(self.output_dir())?;
 
-        // create a calendar
-        let mut calendar = Calendar::new();
-        calendar
-            .name("statical feed")
-            .description("a concatenation of all of the calendars provided to statical")
-            .timezone(&self.calendars.display_timezone().to_string())
-            .append_property(Property::new("METHOD", "PUBLISH"));
+        write_feed(
+            self.calendars,
+            "statical feed",
+            "a concatenation of all of the calendars provided to statical",
+            self.calendars.events(),
+            &self.output_dir().join("feed.ics"),
+        )
+    }
+}
 
-        // loop through all of the events (probably skip the expanded ones)
-        // TODO: write original events with RRules rather than the expanded event recurrences
-        for event in self.calendars.events() {
-            let ical_event = Event::new()
-                .summary(event.summary())
-                .description(event.description())
-                .add_property("CLASS", "PUBLIC")
-                .starts(event.start())
-                .ends(event.end())
-                .done();
+/// Writes the given events to an ICS calendar feed file
+pub(crate) fn write_feed<'a>(
+    calendars: &CalendarCollection,
+    name: &str,
+    description: &str,
+    events: impl Iterator<Item = &'a Rc<StaticalEvent>>,
+    file_path: &Path,
+) -> Result<()> {
+    // create a calendar
+    let mut calendar = Calendar::new();
+    calendar
+        .name(name)
+        .description(description)
+        .timezone(&calendars.display_timezone().to_string())
+        .append_property(Property::new("METHOD", "PUBLISH"));
 
-            // add the event to the calendar
-            calendar.push(ical_event);
+    // loop through all of the events (probably skip the expanded ones)
+    // TODO: write original events with RRules rather than the expanded event recurrences
+    for event in events {
+        let mut ical_event = Event::new();
+        ical_event
+            .summary(event.summary())
+            .description(event.description())
+            .add_property("CLASS", &event.class().to_string());
+        if let Some(status) = event.status() {
+            ical_event.add_property("STATUS", &status.to_string());
         }
+        for category in event.categories() {
+            // commas separate multiple categories so they must be escaped within a single one
+            ical_event.add_multi_property("CATEGORIES", &category.replace(',', "\\,"));
+        }
+        if event.all_day() {
+            ical_event
+                .starts(event.start().date_naive())
+                .ends(event.end().date_naive());
+        } else {
+            ical_event.starts(event.start()).ends(event.end());
+        }
+        let ical_event = ical_event.done();
 
-        // write the calendar feed file to disk
-        // TODO replace this with a debug or log message
-        let file_path = self.output_dir().join("feed.ics");
-        eprintln!("Writing calendar feed to file: {:?}", file_path);
-        let mut output_file = File::create(file_path)?;
-        output_file
-            .write_all(format!("{}", calendar).as_bytes())
-            .wrap_err("could not write calendar feed file")?;
-
-        Ok(())
+        // add the event to the calendar
+        calendar.push(ical_event);
     }
+
+    // write the calendar feed file to disk
+    // TODO replace this with a debug or log message
+    eprintln!("Writing calendar feed to file: {:?}", file_path);
+    let mut output_file = File::create(file_path)?;
+    output_file
+        .write_all(format!("{}", calendar).as_bytes())
+        .wrap_err("could not write calendar feed file")?;
+
+    Ok(())
 }
@@ -1,4 +1,5 @@
 pub mod agenda_view;
+pub mod category_view;
 pub mod day_view;
 pub mod event_view;
 pub mod feed_view;
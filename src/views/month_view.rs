@@ -67,7 +67,7 @@ impl MonthView<'_> {
     ///
     /// This function will return an error if it cannot construct the [`DateRule`] properly.
     // TODO: map the returned values to NaiveDate objects
-    fn months_to_show(&self) -> Result<Vec<Option<Month>>, color_eyre::eyre::Error> {
+    fn months_to_show(&self) -> Result<Vec<Option<Month<'_>>>, color_eyre::eyre::Error> {
         let aligned_month_start = self
             .calendars
             .cal_start
@@ -222,7 +222,7 @@ impl MonthView<'_> {
             .first_event()
             .wrap_err("could not get first event")?
         {
-            context.insert("event_view_path", &first_event.file_path());
+            context.insert("event_view_path", &first_event.page_path(self.config()));
         }
         // context.insert("agenda_view_path", &base_url_path.join("agenda"));
 
@@ -241,7 +241,7 @@ impl MonthView<'_> {
         context.insert("month", &current_month.month());
         context.insert(
             "month_name",
-            &chrono::Month::from_u8(current_month.month() as u8)
+            &chrono::Month::from_u8(current_month.month())
                 .ok_or(eyre!("unknown month"))?
                 .name(),
         );
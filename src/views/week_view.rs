@@ -147,7 +147,7 @@ impl WeekView<'_> {
         context.insert("day_view_path", &current_week.day_view_path());
         // TODO: need to search through the week to find the first event, even if there are not events in the first few days
         if let Some(first_event) = &current_week.first_event() {
-            context.insert("event_view_path", &first_event.file_path());
+            context.insert("event_view_path", &first_event.page_path(self.config()));
         }
         // context.insert("agenda_view_path", &base_url_path.join("agenda"));
 